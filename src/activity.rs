@@ -0,0 +1,48 @@
+use crate::OogaBoogaContract;
+
+/// Last height at which an address was active
+///
+/// Encoded as the last active height and the current height (16 bytes LE
+/// each) and a flag byte that is 1 once the address has been seen at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Activity {
+    pub last_active: u64,
+    pub current_height: u64,
+    pub seen: bool,
+}
+
+impl Activity {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = (self.last_active as u128).to_le_bytes().to_vec();
+        data.extend_from_slice(&(self.current_height as u128).to_le_bytes());
+        data.push(self.seen as u8);
+        data
+    }
+}
+
+// Height of each address's most recent successful mutating operation. An
+// address is active when its balance, allowance or vesting grant changes,
+// or when it spends an allowance.
+impl OogaBoogaContract {
+    // Storage keys
+    pub fn last_active_key(&self, address: &str) -> String {
+        format!("/last-active/{}", address)
+    }
+
+    // Getters
+    pub fn activity(&self, address: &str, current_height: u64) -> Activity {
+        let key = self.last_active_key(address);
+        let recorded = self.read_bytes(&key);
+        Activity {
+            last_active: self.decode_stored_u128(&key, &recorded) as u64,
+            current_height,
+            seen: !recorded.is_empty(),
+        }
+    }
+
+    // Setters
+    /// Staged with the rest of the operation, so failed operations leave it untouched
+    pub(crate) fn mark_active(&self, address: &str, height: u64) {
+        self.write_u128(&self.last_active_key(address), height as u128);
+    }
+}
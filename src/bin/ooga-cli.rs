@@ -0,0 +1,28 @@
+//! Native simulator for the OOGA BOOGA contract.
+//!
+//! Runs opcodes against the mock runtime and persists the storage between
+//! invocations in a JSON file (keys map to hex-encoded values).
+//!
+//! Build and run with:
+//! `cargo run --target <host-triple> --no-default-features --features cli --bin ooga-cli -- <command>`
+
+use std::process::ExitCode;
+
+// The simulator lives in its own module so the `alkanes` build, which has no
+// mock runtime to run it on, can leave all of it out while keeping this
+// entry point
+#[cfg(not(feature = "alkanes"))]
+#[path = "ooga-cli/simulator.rs"]
+mod simulator;
+
+#[cfg(not(feature = "alkanes"))]
+fn main() -> ExitCode {
+    simulator::main()
+}
+
+// The `alkanes` build replaces the mock runtime, so there is nothing to simulate against
+#[cfg(feature = "alkanes")]
+fn main() -> ExitCode {
+    eprintln!("error: ooga-cli runs on the mock runtime; build it with --no-default-features --features cli");
+    ExitCode::from(2)
+}
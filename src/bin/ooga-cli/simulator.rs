@@ -0,0 +1,234 @@
+use anyhow::{Result, anyhow};
+use ooga_booga_contract::test_utils::{extract_u128, payload, restore_storage, snapshot_storage, CallResponse, TestHarness};
+use ooga_booga_contract::{ErrorFrame, InitParams};
+#[cfg(feature = "legacy-responses")]
+use ooga_booga_contract::ErrorCode;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const DEFAULT_STATE_PATH: &str = "ooga-state.json";
+
+// Exit codes: contract and I/O errors, and bad command lines
+const EXIT_FAILED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+
+const USAGE: &str = "usage: ooga-cli [--state <path>] [--caller <address>] [--height <height>] <command>
+
+commands:
+  init [claim-cap] [referral-bonus] [max-supply] [window-start] [window-end] [base-reward] [halving-interval]
+                              initialize the contract
+  claim <address> [referrer]  claim OOGA for an address
+  exchange <address>          exchange 1 OOGA for 1 BOOGA
+  balance <address>           show OOGA and BOOGA balances
+  totals                      show total OOGA and BOOGA supply
+  call <opcode> [inputs...]   run any opcode and print the raw response payload";
+
+struct Options {
+    state: PathBuf,
+    caller: Option<String>,
+    height: u64,
+    command: Vec<String>,
+}
+
+// Error in the command line itself, reported with the usage text
+#[derive(Debug)]
+struct UsageError(String);
+
+impl std::fmt::Display for UsageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+fn parse_args(args: Vec<String>) -> Result<Options> {
+    let mut options = Options {
+        state: PathBuf::from(DEFAULT_STATE_PATH),
+        caller: None,
+        height: 0,
+        command: Vec::new(),
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--state" => {
+                options.state = PathBuf::from(args.next().ok_or_else(|| anyhow!("--state requires a path"))?);
+            },
+            "--caller" => {
+                options.caller = Some(args.next().ok_or_else(|| anyhow!("--caller requires an address"))?);
+            },
+            "--height" => {
+                let height = args.next().ok_or_else(|| anyhow!("--height requires a value"))?;
+                options.height = height.parse().map_err(|_| anyhow!("invalid height: {}", height))?;
+            },
+            _ => {
+                options.command.push(arg);
+                options.command.extend(args.by_ref());
+            },
+        }
+    }
+
+    if options.command.is_empty() {
+        return Err(anyhow!("missing command"));
+    }
+    Ok(options)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(anyhow!("odd-length hex value"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| anyhow!("invalid hex value: {}", hex)))
+        .collect()
+}
+
+fn load_state(path: &Path) -> Result<HashMap<String, Vec<u8>>> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let contents = fs::read_to_string(path)?;
+    let value: serde_json::Value = serde_json::from_str(&contents)?;
+    let object = value.as_object().ok_or_else(|| anyhow!("state file must contain a JSON object"))?;
+
+    let mut entries = HashMap::new();
+    for (key, value) in object {
+        let hex = value.as_str().ok_or_else(|| anyhow!("state value for {} must be a hex string", key))?;
+        entries.insert(key.clone(), decode_hex(hex)?);
+    }
+    Ok(entries)
+}
+
+fn save_state(path: &Path, entries: &HashMap<String, Vec<u8>>) -> Result<()> {
+    let mut object = serde_json::Map::new();
+    let mut keys: Vec<&String> = entries.keys().collect();
+    keys.sort();
+    for key in keys {
+        object.insert(key.clone(), serde_json::Value::String(encode_hex(&entries[key])));
+    }
+    fs::write(path, serde_json::to_string_pretty(&serde_json::Value::Object(object))?)?;
+    Ok(())
+}
+
+fn arg(command: &[String], index: usize, name: &str) -> Result<String> {
+    command.get(index).cloned().ok_or_else(|| anyhow!("missing <{}> argument", name))
+}
+
+// Run an opcode. Legacy responses fail the call with the contract's own
+// error, so frame it here to report it with its code either way
+fn execute(harness: &TestHarness, opcode: u128, inputs: Vec<String>) -> Result<CallResponse> {
+    #[cfg(feature = "legacy-responses")]
+    return harness.execute(opcode, inputs).map_err(|error| ErrorFrame {
+        status: ErrorCode::of(&error) as u8,
+        message: error.to_string(),
+        detail: Vec::new(),
+    }.into());
+    #[cfg(not(feature = "legacy-responses"))]
+    harness.execute(opcode, inputs)
+}
+
+fn run_command(harness: &TestHarness, command: &[String]) -> Result<()> {
+    match command[0].as_str() {
+        "init" => {
+            let response = execute(harness, 0, command[1..].to_vec())?;
+            let params = InitParams::decode(payload(&response))?;
+            println!("initialized: schema version {}, claim cap {}, owner {}", params.schema_version, params.claim_cap, params.owner);
+            println!(
+                "referral bonus {}, max supply {}, claim window {}..{}, base reward {}, halving interval {}",
+                params.referral_bonus, params.max_supply, params.claim_window.start, params.claim_window.end,
+                params.base_reward, params.halving_interval
+            );
+        },
+        "claim" => {
+            let address = arg(command, 1, "address")?;
+            let before = extract_u128(&execute(harness, 3, vec![address.clone()])?);
+            execute(harness, 1, command[1..].to_vec())?;
+            let balance = extract_u128(&execute(harness, 3, vec![address.clone()])?);
+            println!("claimed {} OOGA for {} (OOGA balance {})", balance - before, address, balance);
+        },
+        "exchange" => {
+            let address = arg(command, 1, "address")?;
+            execute(harness, 2, vec![address.clone()])?;
+            let ooga = extract_u128(&execute(harness, 3, vec![address.clone()])?);
+            let booga = extract_u128(&execute(harness, 4, vec![address.clone()])?);
+            println!("exchanged 1 OOGA for 1 BOOGA for {} (OOGA {}, BOOGA {})", address, ooga, booga);
+        },
+        "balance" => {
+            let address = arg(command, 1, "address")?;
+            let ooga = extract_u128(&execute(harness, 3, vec![address.clone()])?);
+            let booga = extract_u128(&execute(harness, 4, vec![address.clone()])?);
+            println!("{}: OOGA {}, BOOGA {}", address, ooga, booga);
+        },
+        "totals" => {
+            let ooga = extract_u128(&execute(harness, 5, vec![])?);
+            let booga = extract_u128(&execute(harness, 6, vec![])?);
+            println!("total OOGA: {}", ooga);
+            println!("total BOOGA: {}", booga);
+        },
+        "call" => {
+            let opcode = arg(command, 1, "opcode")?;
+            let opcode: u128 = opcode.parse().map_err(|_| anyhow!("invalid opcode: {}", opcode))?;
+            let response = execute(harness, opcode, command[2..].to_vec())?;
+            println!("{}", encode_hex(payload(&response)));
+        },
+        other => return Err(UsageError(format!("unknown command: {}", other)).into()),
+    }
+    Ok(())
+}
+
+fn run(options: Options) -> Result<()> {
+    let harness = TestHarness::new();
+    restore_storage(load_state(&options.state)?);
+    harness.set_height(options.height);
+    if let Some(caller) = &options.caller {
+        harness.set_caller(caller);
+    }
+
+    // State is only written back when the command succeeds, like a reverted transaction
+    run_command(&harness, &options.command)?;
+    save_state(&options.state, &snapshot_storage())
+}
+
+// Contract errors are shown with their error code
+fn report(error: &anyhow::Error) {
+    match error.downcast_ref::<ErrorFrame>() {
+        Some(frame) => match frame.code() {
+            Some(code) => eprintln!("error: {:?}: {}", code, frame.message),
+            None => eprintln!("error: status {}: {}", frame.status, frame.message),
+        },
+        None => eprintln!("error: {}", error),
+    }
+}
+
+pub fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("{}", USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        },
+    };
+
+    match run(options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if e.is::<UsageError>() => {
+            eprintln!("error: {}", e);
+            eprintln!("{}", USAGE);
+            ExitCode::from(EXIT_USAGE)
+        },
+        Err(e) => {
+            report(&e);
+            ExitCode::from(EXIT_FAILED)
+        },
+    }
+}
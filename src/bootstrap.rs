@@ -0,0 +1,76 @@
+use anyhow::{Result, anyhow};
+
+use crate::{AlreadyInitialized, OogaBoogaContract};
+
+/// Most (address, OOGA, BOOGA) entries accepted by one bootstrap call, so a
+/// single batch stays well within the WASM fuel budget
+pub const BOOTSTRAP_BATCH_LIMIT: usize = 25;
+
+// Balances carried over from a previous deployment. Bootstrap batches may
+// only run before initialization; the first one records its caller as the
+// owner, and the owner's init call finalizes the bootstrap. Until then every
+// other mutating opcode is rejected.
+impl OogaBoogaContract {
+    // Storage keys
+    pub fn bootstrapping_key(&self) -> String {
+        "/bootstrapping".to_string()
+    }
+
+    // Getters
+    /// Whether bootstrap batches have been loaded but init has not finalized them
+    pub fn is_bootstrapping(&self) -> bool {
+        self.read_u128(&self.bootstrapping_key()) != 0
+    }
+
+    // Setters
+    pub fn set_bootstrapping(&self, bootstrapping: bool) {
+        if bootstrapping {
+            self.write_u128(&self.bootstrapping_key(), 1);
+        } else {
+            self.remove(&self.bootstrapping_key());
+        }
+    }
+
+    // Bootstrap operations
+    /// Credit one batch of balances, returning the OOGA and BOOGA totals so far.
+    /// Entries add to any balance the address already holds, so an address
+    /// may appear more than once.
+    pub(crate) fn bootstrap_balances(&self, caller: &str, entries: &[(String, u128, u128)], height: u64) -> Result<(u128, u128)> {
+        if self.is_initialized() {
+            return Err(AlreadyInitialized(self.init_params()).into());
+        }
+        if entries.is_empty() {
+            return Err(anyhow!("no bootstrap entries"));
+        }
+        if entries.len() > BOOTSTRAP_BATCH_LIMIT {
+            return Err(anyhow!("bootstrap batch of {} entries exceeds limit of {}", entries.len(), BOOTSTRAP_BATCH_LIMIT));
+        }
+        if self.is_bootstrapping() {
+            self.require_owner(caller)?;
+        } else {
+            self.set_owner(caller);
+            self.set_bootstrapping(true);
+        }
+
+        let mut total_ooga = self.total_ooga();
+        let mut total_booga = self.total_booga();
+        for (address, ooga, booga) in entries {
+            let ooga_balance = self.ooga_balance_of(address).checked_add(*ooga)
+                .ok_or_else(|| anyhow!("balance overflow"))?;
+            let booga_balance = self.booga_balance_of(address).checked_add(*booga)
+                .ok_or_else(|| anyhow!("balance overflow"))?;
+            total_ooga = total_ooga.checked_add(*ooga)
+                .ok_or_else(|| anyhow!("supply overflow"))?;
+            total_booga = total_booga.checked_add(*booga)
+                .ok_or_else(|| anyhow!("supply overflow"))?;
+
+            self.set_ooga_balance(address, ooga_balance);
+            self.set_booga_balance(address, booga_balance);
+            self.mark_active(address, height);
+        }
+        self.set_total_ooga(total_ooga);
+        self.set_total_booga(total_booga);
+
+        Ok((total_ooga, total_booga))
+    }
+}
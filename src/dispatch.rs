@@ -0,0 +1,536 @@
+use anyhow::{Result, anyhow};
+
+use crate::events::encode_event_page;
+use crate::inputs::{decode_amount_nonzero, decode_index, decode_limit, is_range_error, LimitOutOfRange, MAX_PAGE_SIZE};
+use crate::response::{InvalidInput, UnrecognizedOpcode};
+use crate::{ClaimPayment, ClaimWindow, OogaBoogaContract, RewardSchedule, SwapOffer, Token};
+#[cfg(feature = "alkanes")]
+use alkanes_support::id::AlkaneId;
+#[cfg(feature = "alkanes")]
+use alkanes_support::utils::shift_or_err;
+#[cfg(all(any(test, feature = "cli"), not(feature = "alkanes")))]
+use crate::test_utils::{shift_or_err, AlkaneId};
+
+/// Every opcode the contract accepts
+///
+/// The dispatch table below must have an entry for each variant: its match
+/// has no fallback arm, so a missing entry fails to compile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Initialize = 0,
+    Claim = 1,
+    Exchange = 2,
+    OogaBalance = 3,
+    BoogaBalance = 4,
+    TotalOoga = 5,
+    TotalBooga = 6,
+    ClaimCap = 7,
+    CreateVestingGrant = 8,
+    ClaimVested = 9,
+    VestingGrant = 10,
+    ApproveOoga = 11,
+    OogaAllowance = 12,
+    TransferOogaFrom = 13,
+    ApproveBooga = 14,
+    BoogaAllowance = 15,
+    TransferBoogaFrom = 16,
+    OogaSpendable = 17,
+    BoogaSpendable = 18,
+    AdjustBalance = 19,
+    EventsSince = 20,
+    CheckInvariants = 21,
+    AddressEvents = 22,
+    Referrer = 23,
+    ReferralEarnings = 24,
+    ClaimWindow = 25,
+    TransferMany = 26,
+    HolderRank = 27,
+    SetClaimPrice = 28,
+    ClaimPrice = 29,
+    LastActive = 30,
+    Bootstrap = 31,
+    QuoteExchange = 32,
+    ClaimReward = 33,
+    OfferSwap = 34,
+    Swap = 35,
+    SwapOffer = 36,
+}
+
+/// Decoders for opcode inputs, implemented by each input flavor: u128 values
+/// on the Alkanes runtime and strings on the mock runtime. The mock runtime
+/// also accepts u128 values, so tests can check both flavors agree.
+pub trait OpcodeInputs {
+    fn is_empty(&self) -> bool;
+    fn address(&mut self) -> Result<String>;
+    fn number(&mut self) -> Result<u128>;
+
+    fn number_or(&mut self, default: u128) -> Result<u128> {
+        if self.is_empty() {
+            return Ok(default);
+        }
+        self.number()
+    }
+
+    fn height(&mut self) -> Result<u64> {
+        u64::try_from(self.number()?).map_err(|_| anyhow!("invalid numeric input"))
+    }
+
+    fn height_or(&mut self, default: u64) -> Result<u64> {
+        if self.is_empty() {
+            return Ok(default);
+        }
+        self.height()
+    }
+
+    fn amount_nonzero(&mut self) -> Result<u128> {
+        decode_amount_nonzero(self.number()?)
+    }
+
+    fn amount_nonzero_or(&mut self, default: u128) -> Result<u128> {
+        decode_amount_nonzero(self.number_or(default)?)
+    }
+
+    fn index(&mut self) -> Result<u64> {
+        decode_index(self.number()?)
+    }
+
+    fn index_or(&mut self, default: u64) -> Result<u64> {
+        if self.is_empty() {
+            return Ok(default);
+        }
+        self.index()
+    }
+
+    /// Page size of at most `max`
+    fn limit(&mut self, max: u128) -> Result<u128> {
+        decode_limit(self.number()?, max)
+    }
+
+    fn optional_limit(&mut self, max: u128) -> Result<Option<u128>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        self.limit(max).map(Some)
+    }
+
+    fn optional_address(&mut self) -> Result<Option<String>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        self.address().map(Some)
+    }
+
+    fn token(&mut self) -> Result<Token> {
+        Token::from_code(self.number()?)
+    }
+
+    fn alkane_id(&mut self) -> Result<AlkaneId> {
+        Ok(AlkaneId {
+            block: self.number()?,
+            tx: self.number()?,
+        })
+    }
+
+    /// Every remaining input as (address, OOGA amount, BOOGA amount) triples
+    fn balance_entries(&mut self) -> Result<Vec<(String, u128, u128)>> {
+        let mut entries = Vec::new();
+        while !self.is_empty() {
+            let address = self.address()?;
+            let ooga = self.number()?;
+            let booga = self.number()?;
+            entries.push((address, ooga, booga));
+        }
+        Ok(entries)
+    }
+
+    /// Every remaining input as (recipient, nonzero amount) pairs, at most `max` of them
+    fn payments(&mut self, max: u128) -> Result<Vec<(String, u128)>> {
+        let mut payments = Vec::new();
+        while !self.is_empty() {
+            if payments.len() as u128 == max {
+                return Err(LimitOutOfRange { limit: max + 1, max }.into());
+            }
+            let recipient = self.address()?;
+            let amount = self.amount_nonzero()?;
+            payments.push((recipient, amount));
+        }
+        Ok(payments)
+    }
+}
+
+/// Canonical address of an alkane, "block:tx". The Alkanes runtime names the
+/// caller this way and u128 address inputs are (block, tx) pairs formatted the
+/// same, so a caller and an input naming it resolve to the same storage keys.
+pub fn alkane_address(id: &AlkaneId) -> String {
+    format!("{}:{}", id.block, id.tx)
+}
+
+impl OpcodeInputs for Vec<u128> {
+    fn is_empty(&self) -> bool {
+        self.as_slice().is_empty()
+    }
+
+    fn address(&mut self) -> Result<String> {
+        Ok(alkane_address(&self.alkane_id()?))
+    }
+
+    fn number(&mut self) -> Result<u128> {
+        shift_or_err(self)
+    }
+}
+
+/// Call details shared by both runtimes
+pub struct Call<I: OpcodeInputs> {
+    pub inputs: I,
+    pub caller: String,
+    pub height: u64,
+    pub incoming_alkanes: Vec<(AlkaneId, u128)>,
+}
+
+/// Response produced by an opcode
+///
+/// `alkanes` replaces the outgoing transfers when set; otherwise every
+/// incoming transfer is forwarded back unchanged.
+#[derive(Debug, Default)]
+pub struct Output {
+    pub data: Vec<u8>,
+    pub alkanes: Option<Vec<(AlkaneId, u128)>>,
+}
+
+/// Response encoders for the values opcode handlers return
+pub trait IntoOutput {
+    fn into_output(self) -> Output;
+}
+
+impl IntoOutput for () {
+    fn into_output(self) -> Output {
+        Output::default()
+    }
+}
+
+impl IntoOutput for u128 {
+    fn into_output(self) -> Output {
+        self.to_le_bytes().to_vec().into_output()
+    }
+}
+
+impl IntoOutput for Vec<u8> {
+    fn into_output(self) -> Output {
+        Output {
+            data: self,
+            alkanes: None,
+        }
+    }
+}
+
+// Paid claims return the settlement and send the refunds; free claims forward everything
+impl IntoOutput for Option<ClaimPayment> {
+    fn into_output(self) -> Output {
+        match self {
+            Some(payment) => Output {
+                data: payment.encode(),
+                alkanes: Some(payment.refunds),
+            },
+            None => Output::default(),
+        }
+    }
+}
+
+impl Opcode {
+    pub fn code(&self) -> u128 {
+        *self as u128
+    }
+}
+
+// Records a decoded input in the harness trace; expands to nothing unless
+// the mock runtime is built with the `trace` feature
+#[cfg(all(feature = "trace", any(test, feature = "cli"), not(feature = "alkanes")))]
+macro_rules! trace_param {
+    ($name:ident) => {
+        crate::test_utils::trace::record_param(stringify!($name), format!("{:?}", $name))
+    };
+}
+
+#[cfg(not(all(feature = "trace", any(test, feature = "cli"), not(feature = "alkanes"))))]
+macro_rules! trace_param {
+    ($name:ident) => {};
+}
+
+// Decoder failures are reported as `InvalidInput`, except range check
+// failures, which keep their own error codes
+fn input_error(error: anyhow::Error) -> anyhow::Error {
+    if is_range_error(&error) {
+        return error;
+    }
+    InvalidInput(error.to_string()).into()
+}
+
+// Expands the table into `Opcode::from_code`, `Opcode::ALL`,
+// `Opcode::is_mutating` and the handler lookup behind `dispatch`.
+// Each entry lists the opcode number, whether it `reads` storage only or also
+// `writes` it, its variant, the inputs in order with their decoder (and its
+// argument: the default of an optional trailing input, or a bound) and the
+// handler body, whose result is encoded through `IntoOutput`.
+macro_rules! opcodes {
+    (@writes reads) => { false };
+    (@writes writes) => { true };
+
+    ($contract:ident, $call:ident;
+     $( $code:literal => $access:ident $variant:ident ( $( $arg:ident : $decoder:ident $( ( $default:expr ) )? ),* ) $body:block )*) => {
+        // Table numbers must match the enum discriminants
+        const _: () = {
+            $( assert!(Opcode::$variant as u128 == $code); )*
+        };
+
+        impl Opcode {
+            pub const ALL: &'static [Opcode] = &[$( Opcode::$variant ),*];
+
+            pub fn from_code(code: u128) -> Result<Self> {
+                match code {
+                    $( $code => Ok(Opcode::$variant), )*
+                    _ => Err(UnrecognizedOpcode.into()),
+                }
+            }
+
+            /// Whether the opcode can write to storage
+            pub fn is_mutating(&self) -> bool {
+                match self {
+                    $( Opcode::$variant => opcodes!(@writes $access), )*
+                }
+            }
+        }
+
+        // One handler per opcode, named after its variant
+        #[allow(non_snake_case, unused_variables)]
+        mod handlers {
+            use super::*;
+
+            $(
+                pub(super) fn $variant<I: OpcodeInputs>($contract: &OogaBoogaContract, $call: &mut Call<I>) -> Result<impl IntoOutput> {
+                    $( let $arg = $call.inputs.$decoder($( $default )?)
+                        .map_err(input_error)?;
+                       trace_param!($arg); )*
+                    $body
+                }
+            )*
+        }
+
+        fn run_handler<I: OpcodeInputs>(contract: &OogaBoogaContract, opcode: Opcode, call: &mut Call<I>) -> Result<Output> {
+            match opcode {
+                $( Opcode::$variant => handlers::$variant(contract, call).map(IntoOutput::into_output), )*
+            }
+        }
+    };
+}
+
+/// Decode the inputs of an opcode, run its handler and encode the response
+pub(crate) fn dispatch<I: OpcodeInputs>(contract: &OogaBoogaContract, opcode: Opcode, call: &mut Call<I>) -> Result<Output> {
+    // A pending bootstrap only accepts further batches and the init that finalizes it
+    if opcode.is_mutating() && !matches!(opcode, Opcode::Initialize | Opcode::Bootstrap) && contract.is_bootstrapping() {
+        return Err(anyhow!("bootstrap in progress; initialize the contract to finalize it"));
+    }
+    run_handler(contract, opcode, call)
+}
+
+opcodes! { contract, call;
+    // Initialize the contract, echoing the recorded parameters. The claim reward
+    // schedule starts counting halvings at the initialization height.
+    0 => writes Initialize(claim_cap: number_or(0), referral_bonus: number_or(0), max_supply: number_or(0), window_start: height_or(0), window_end: height_or(0), base_reward: number_or(1), halving_interval: height_or(0)) {
+        let window = ClaimWindow { start: window_start, end: window_end };
+        let schedule = RewardSchedule { base_reward, halving_interval, start: call.height };
+        let params = contract.initialize_contract(claim_cap, referral_bonus, max_supply, window, schedule, &call.caller)?;
+        Ok(params.encode())
+    }
+
+    // Claim OOGA, optionally naming a referrer; paid claims refund any surplus
+    1 => writes Claim(address: address, referrer: optional_address) {
+        contract.claim_ooga(&address, referrer.as_deref(), call.height)?;
+        if contract.claim_price() == 0 {
+            return Ok(None);
+        }
+        contract.settle_claim_payment(&call.incoming_alkanes).map(Some)
+    }
+
+    // Exchange OOGA for BOOGA at 1:1, one unit unless an amount is given
+    2 => writes Exchange(address: address, amount: amount_nonzero_or(1)) {
+        contract.exchange_ooga_for_booga(&address, amount, call.height)
+    }
+
+    3 => reads OogaBalance(address: address) {
+        Ok(contract.ooga_balance_of(&address))
+    }
+
+    4 => reads BoogaBalance(address: address) {
+        Ok(contract.booga_balance_of(&address))
+    }
+
+    5 => reads TotalOoga() {
+        Ok(contract.total_ooga())
+    }
+
+    6 => reads TotalBooga() {
+        Ok(contract.total_booga())
+    }
+
+    // Per-block claim cap and claims in the current block
+    7 => reads ClaimCap() {
+        let mut data = contract.claim_cap().to_le_bytes().to_vec();
+        data.extend_from_slice(&contract.claims_at_height(call.height).to_le_bytes());
+        Ok(data)
+    }
+
+    // Owner only
+    8 => writes CreateVestingGrant(beneficiary: address, total: amount_nonzero, start: height, duration: height) {
+        contract.create_vesting_grant(&call.caller, &beneficiary, total, start, duration, call.height)
+    }
+
+    9 => writes ClaimVested(address: address) {
+        contract.claim_vested(&address, call.height)
+    }
+
+    // Vesting grant total, claimed and claimable
+    10 => reads VestingGrant(address: address) {
+        let grant = contract.vesting_grant(&address)
+            .ok_or_else(|| anyhow!("no vesting grant for address"))?;
+        let mut data = grant.total.to_le_bytes().to_vec();
+        data.extend_from_slice(&grant.claimed.to_le_bytes());
+        data.extend_from_slice(&grant.claimable(call.height).to_le_bytes());
+        Ok(data)
+    }
+
+    // An amount of 0 revokes the approval, so it is not decoded as nonzero
+    11 => writes ApproveOoga(spender: address, amount: number) {
+        contract.approve(Token::Ooga, &call.caller, &spender, amount, call.height)
+    }
+
+    12 => reads OogaAllowance(owner: address, spender: address) {
+        Ok(contract.allowance(Token::Ooga, &owner, &spender))
+    }
+
+    13 => writes TransferOogaFrom(owner: address, recipient: address, amount: amount_nonzero) {
+        contract.transfer_from(Token::Ooga, &call.caller, &owner, &recipient, amount, call.height)
+    }
+
+    // 0 revokes, as for OOGA
+    14 => writes ApproveBooga(spender: address, amount: number) {
+        contract.approve(Token::Booga, &call.caller, &spender, amount, call.height)
+    }
+
+    15 => reads BoogaAllowance(owner: address, spender: address) {
+        Ok(contract.allowance(Token::Booga, &owner, &spender))
+    }
+
+    16 => writes TransferBoogaFrom(owner: address, recipient: address, amount: amount_nonzero) {
+        contract.transfer_from(Token::Booga, &call.caller, &owner, &recipient, amount, call.height)
+    }
+
+    // Allowance capped by the owner's balance
+    17 => reads OogaSpendable(owner: address, spender: address) {
+        Ok(contract.spendable(Token::Ooga, &owner, &spender))
+    }
+
+    18 => reads BoogaSpendable(owner: address, spender: address) {
+        Ok(contract.spendable(Token::Booga, &owner, &spender))
+    }
+
+    // Force a balance to an explicit value (owner only), returning the event sequence number
+    19 => writes AdjustBalance(token: token, address: address, new_value: number, reason: number) {
+        contract.adjust_balance(&call.caller, token, &address, new_value, reason, call.height)
+    }
+
+    20 => reads EventsSince(start: index, limit: limit(MAX_PAGE_SIZE)) {
+        Ok(encode_event_page(&contract.events_since(start.into(), limit)?))
+    }
+
+    // Holder balance sums against the totals, one page of holders from `start`
+    // at a time; the limit defaults to a full page
+    21 => reads CheckInvariants(start: index_or(0), limit: optional_limit(MAX_PAGE_SIZE), ooga_carry: number_or(0), booga_carry: number_or(0)) {
+        Ok(contract.check_invariants_page(start.into(), limit.unwrap_or(MAX_PAGE_SIZE), ooga_carry, booga_carry).encode())
+    }
+
+    22 => reads AddressEvents(address: address, start: index, limit: limit(MAX_PAGE_SIZE)) {
+        Ok(encode_event_page(&contract.events_for_address(&address, start.into(), limit)?))
+    }
+
+    // Recorded referrer, empty if none
+    23 => reads Referrer(address: address) {
+        Ok(contract.referrer_of(&address).unwrap_or_default().into_bytes())
+    }
+
+    24 => reads ReferralEarnings(address: address) {
+        Ok(contract.referral_earnings(&address))
+    }
+
+    // Window bounds and whether it is open now
+    25 => reads ClaimWindow() {
+        Ok(contract.claim_window().encode(call.height))
+    }
+
+    // Pay repeated (recipient, amount) pairs from the caller's OOGA, at most a page of them
+    26 => writes TransferMany(payments: payments(MAX_PAGE_SIZE)) {
+        let (paid, remaining) = contract.transfer_many(Token::Ooga, &call.caller, &payments, call.height)?;
+        let mut data = paid.to_le_bytes().to_vec();
+        data.extend_from_slice(&remaining.to_le_bytes());
+        Ok(data)
+    }
+
+    27 => reads HolderRank(address: address) {
+        Ok(contract.holder_rank(&address)?.encode())
+    }
+
+    // Owner only
+    28 => writes SetClaimPrice(token: alkane_id, price: number) {
+        contract.configure_claim_price(&call.caller, token, price)
+    }
+
+    // Payment token and price
+    29 => reads ClaimPrice() {
+        let token = contract.claim_payment_token();
+        let mut data = token.block.to_le_bytes().to_vec();
+        data.extend_from_slice(&token.tx.to_le_bytes());
+        data.extend_from_slice(&contract.claim_price().to_le_bytes());
+        Ok(data)
+    }
+
+    // Last active height alongside the current height
+    30 => reads LastActive(address: address) {
+        Ok(contract.activity(&address, call.height).encode())
+    }
+
+    // Load repeated (address, OOGA, BOOGA) balances before init, returning the totals so far
+    31 => writes Bootstrap(entries: balance_entries) {
+        let (total_ooga, total_booga) = contract.bootstrap_balances(&call.caller, &entries, call.height)?;
+        let mut data = total_ooga.to_le_bytes().to_vec();
+        data.extend_from_slice(&total_booga.to_le_bytes());
+        Ok(data)
+    }
+
+    // What the same exchange would debit, credit and charge, without writing
+    32 => reads QuoteExchange(address: address, amount: amount_nonzero_or(1)) {
+        Ok(contract.quote_exchange(&address, amount).encode())
+    }
+
+    // Reward for a claim now and the height of the next halving
+    33 => reads ClaimReward() {
+        Ok(contract.reward_schedule().encode(call.height))
+    }
+
+    // Offer `give_amount` of a token for `want_amount` of the other until `expiry`.
+    // A `give_amount` of 0 withdraws the standing offer instead, ignoring the
+    // other inputs, so neither amount is decoded as nonzero here; an offer that
+    // gives something must want a nonzero amount, which the handler checks.
+    34 => writes OfferSwap(give: token, give_amount: number, want_amount: number, expiry: height) {
+        let offer = SwapOffer { give, give_amount, want_amount, expiry };
+        contract.offer_swap(&call.caller, offer, call.height)
+    }
+
+    // Take the counterparty's standing offer, trading both legs at once
+    35 => writes Swap(counterparty: address, give_amount: amount_nonzero, receive_amount: amount_nonzero) {
+        contract.swap(&call.caller, &counterparty, give_amount, receive_amount, call.height)
+    }
+
+    // Standing offer, empty if none
+    36 => reads SwapOffer(address: address) {
+        Ok(contract.swap_offer(&address).map(|offer| offer.encode()).unwrap_or_default())
+    }
+}
@@ -0,0 +1,182 @@
+use anyhow::{Result, anyhow};
+
+use crate::{OogaBoogaContract, Token};
+
+/// Kinds of records appended to the event log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// Owner forced a balance to an explicit value
+    BalanceAdjusted = 1,
+}
+
+impl EventKind {
+    pub fn from_code(code: u8) -> Result<Self> {
+        match code {
+            1 => Ok(EventKind::BalanceAdjusted),
+            _ => Err(anyhow!("unknown event kind")),
+        }
+    }
+}
+
+/// A single event log record
+///
+/// Encoded as kind (1 byte), token (1 byte), height (8 bytes LE), old value,
+/// new value and reason code (16 bytes LE each), followed by the address bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub kind: EventKind,
+    pub token: Token,
+    pub address: String,
+    pub old_value: u128,
+    pub new_value: u128,
+    pub height: u64,
+    pub reason: u128,
+}
+
+const EVENT_HEADER_LEN: usize = 58;
+
+impl Event {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = vec![self.kind as u8, self.token.code()];
+        data.extend_from_slice(&self.height.to_le_bytes());
+        data.extend_from_slice(&self.old_value.to_le_bytes());
+        data.extend_from_slice(&self.new_value.to_le_bytes());
+        data.extend_from_slice(&self.reason.to_le_bytes());
+        data.extend_from_slice(self.address.as_bytes());
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < EVENT_HEADER_LEN {
+            return Err(anyhow!("event record too short"));
+        }
+        let mut height = [0u8; 8];
+        height.copy_from_slice(&data[2..10]);
+        let mut values = [[0u8; 16]; 3];
+        for (i, value) in values.iter_mut().enumerate() {
+            value.copy_from_slice(&data[10 + i * 16..26 + i * 16]);
+        }
+        Ok(Event {
+            kind: EventKind::from_code(data[0])?,
+            token: Token::from_code(data[1] as u128)?,
+            height: u64::from_le_bytes(height),
+            old_value: u128::from_le_bytes(values[0]),
+            new_value: u128::from_le_bytes(values[1]),
+            reason: u128::from_le_bytes(values[2]),
+            address: String::from_utf8(data[EVENT_HEADER_LEN..].to_vec())
+                .map_err(|_| anyhow!("event address is not valid UTF-8"))?,
+        })
+    }
+}
+
+/// Encode event log entries for a query response: each entry is the sequence
+/// number (16 bytes LE), the record length (4 bytes LE) and the record itself.
+pub fn encode_event_page(entries: &[(u128, Event)]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for (sequence, event) in entries {
+        let record = event.encode();
+        data.extend_from_slice(&sequence.to_le_bytes());
+        data.extend_from_slice(&(record.len() as u32).to_le_bytes());
+        data.extend_from_slice(&record);
+    }
+    data
+}
+
+/// Decode a response produced by `encode_event_page`
+pub fn decode_event_page(data: &[u8]) -> Result<Vec<(u128, Event)>> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        if data.len() - offset < 20 {
+            return Err(anyhow!("truncated event page"));
+        }
+        let mut sequence = [0u8; 16];
+        sequence.copy_from_slice(&data[offset..offset + 16]);
+        let mut length = [0u8; 4];
+        length.copy_from_slice(&data[offset + 16..offset + 20]);
+        let length = u32::from_le_bytes(length) as usize;
+        offset += 20;
+        if data.len() - offset < length {
+            return Err(anyhow!("truncated event page"));
+        }
+        entries.push((u128::from_le_bytes(sequence), Event::decode(&data[offset..offset + length])?));
+        offset += length;
+    }
+    Ok(entries)
+}
+
+// Event log storage
+impl OogaBoogaContract {
+    // Storage keys
+    pub fn event_count_key(&self) -> String {
+        "/event-count".to_string()
+    }
+
+    pub fn event_key(&self, sequence: u128) -> String {
+        format!("/events/{}", sequence)
+    }
+
+    pub fn address_event_count_key(&self, address: &str) -> String {
+        format!("/events-by-address/{}/count", address)
+    }
+
+    /// Sequence number of the address's `index`-th event
+    pub fn address_event_key(&self, address: &str, index: u128) -> String {
+        format!("/events-by-address/{}/{}", address, index)
+    }
+
+    // Getters
+    pub fn event_count(&self) -> u128 {
+        self.read_u128(&self.event_count_key())
+    }
+
+    pub fn event(&self, sequence: u128) -> Result<Event> {
+        if sequence >= self.event_count() {
+            return Err(anyhow!("event not found"));
+        }
+        Event::decode(&self.read_bytes(&self.event_key(sequence)))
+    }
+
+    /// Events with sequence numbers from `start`, at most `limit` of them
+    pub fn events_since(&self, start: u128, limit: u128) -> Result<Vec<(u128, Event)>> {
+        let end = self.event_count().min(start.saturating_add(limit));
+        let mut entries = Vec::new();
+        let mut sequence = start;
+        while sequence < end {
+            entries.push((sequence, Event::decode(&self.read_bytes(&self.event_key(sequence)))?));
+            sequence += 1;
+        }
+        Ok(entries)
+    }
+
+    pub fn address_event_count(&self, address: &str) -> u128 {
+        self.read_u128(&self.address_event_count_key(address))
+    }
+
+    /// Events involving the address from its `start`-th one, at most `limit` of them
+    pub fn events_for_address(&self, address: &str, start: u128, limit: u128) -> Result<Vec<(u128, Event)>> {
+        let end = self.address_event_count(address).min(start.saturating_add(limit));
+        let mut entries = Vec::new();
+        let mut index = start;
+        while index < end {
+            let sequence = self.read_u128(&self.address_event_key(address, index));
+            entries.push((sequence, Event::decode(&self.read_bytes(&self.event_key(sequence)))?));
+            index += 1;
+        }
+        Ok(entries)
+    }
+
+    // Setters
+    /// Append an event to the log and the address index, returning its sequence number.
+    /// Both are staged in the same batch, so they are committed together.
+    pub(crate) fn append_event(&self, event: &Event) -> u128 {
+        let sequence = self.event_count();
+        self.write_bytes(&self.event_key(sequence), event.encode());
+        self.write_u128(&self.event_count_key(), sequence + 1);
+
+        let index = self.address_event_count(&event.address);
+        self.write_u128(&self.address_event_key(&event.address, index), sequence);
+        self.write_u128(&self.address_event_count_key(&event.address), index + 1);
+        sequence
+    }
+}
@@ -0,0 +1,110 @@
+use crate::OogaBoogaContract;
+
+/// Fee charged on an exchange, in OOGA; the exchange is currently free
+pub const EXCHANGE_FEE: u128 = 0;
+
+/// Balances an exchange reads before computing its plan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExchangeBalances {
+    pub ooga_balance: u128,
+    pub booga_balance: u128,
+    pub total_ooga: u128,
+    pub total_booga: u128,
+}
+
+/// Effect of exchanging OOGA for BOOGA, computed without touching storage
+///
+/// `balances` holds the state the exchange leaves behind. When the exchange
+/// would fail, `failure` gives the reason and nothing is debited or credited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExchangePlan {
+    pub ooga_debited: u128,
+    pub booga_credited: u128,
+    pub fee: u128,
+    pub balances: ExchangeBalances,
+    pub failure: Option<String>,
+}
+
+impl ExchangePlan {
+    pub fn is_ok(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    /// Encoded as the OOGA debited, BOOGA credited and fee (16 bytes LE each),
+    /// a flag byte that is 1 when the exchange would succeed, and the UTF-8
+    /// failure reason otherwise
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = self.ooga_debited.to_le_bytes().to_vec();
+        data.extend_from_slice(&self.booga_credited.to_le_bytes());
+        data.extend_from_slice(&self.fee.to_le_bytes());
+        data.push(self.is_ok() as u8);
+        if let Some(failure) = &self.failure {
+            data.extend_from_slice(failure.as_bytes());
+        }
+        data
+    }
+
+    fn failed(balances: ExchangeBalances, reason: &str) -> Self {
+        ExchangePlan {
+            ooga_debited: 0,
+            booga_credited: 0,
+            fee: 0,
+            balances,
+            failure: Some(reason.to_string()),
+        }
+    }
+}
+
+/// Plan an exchange of `amount` OOGA (plus the fee) for `amount` BOOGA at 1:1.
+/// Both the quote and the exchange itself use this, so they always agree.
+pub fn compute_exchange(amount: u128, balances: ExchangeBalances) -> ExchangePlan {
+    if amount == 0 {
+        return ExchangePlan::failed(balances, "exchange amount must be nonzero");
+    }
+    let debited = match amount.checked_add(EXCHANGE_FEE) {
+        Some(debited) if debited <= balances.ooga_balance => debited,
+        _ => return ExchangePlan::failed(balances, "insufficient OOGA balance"),
+    };
+    let booga_balance = match balances.booga_balance.checked_add(amount) {
+        Some(booga_balance) => booga_balance,
+        None => return ExchangePlan::failed(balances, "balance overflow"),
+    };
+    let total_booga = match balances.total_booga.checked_add(amount) {
+        Some(total_booga) => total_booga,
+        None => return ExchangePlan::failed(balances, "supply overflow"),
+    };
+    let total_ooga = match balances.total_ooga.checked_sub(debited) {
+        Some(total_ooga) => total_ooga,
+        None => return ExchangePlan::failed(balances, "supply underflow"),
+    };
+
+    ExchangePlan {
+        ooga_debited: debited,
+        booga_credited: amount,
+        fee: EXCHANGE_FEE,
+        balances: ExchangeBalances {
+            ooga_balance: balances.ooga_balance - debited,
+            booga_balance,
+            total_ooga,
+            total_booga,
+        },
+        failure: None,
+    }
+}
+
+// Read-only exchange quotes
+impl OogaBoogaContract {
+    pub fn exchange_balances(&self, address: &str) -> ExchangeBalances {
+        ExchangeBalances {
+            ooga_balance: self.ooga_balance_of(address),
+            booga_balance: self.booga_balance_of(address),
+            total_ooga: self.total_ooga(),
+            total_booga: self.total_booga(),
+        }
+    }
+
+    /// What exchanging `amount` OOGA would do for the address right now
+    pub fn quote_exchange(&self, address: &str, amount: u128) -> ExchangePlan {
+        compute_exchange(amount, self.exchange_balances(address))
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::{Result, anyhow};
+
+use crate::inputs::MAX_PAGE_SIZE;
+use crate::OogaBoogaContract;
+
+/// Most holder index entries a rank query will scan. The scan reads an
+/// address and a balance per entry, so this keeps a rank query within the
+/// storage traffic of the other page-sized queries.
+pub const RANK_SCAN_LIMIT: u128 = MAX_PAGE_SIZE;
+
+/// An address's standing among nonzero OOGA holders
+///
+/// Encoded as the balance, the 1-based rank (0 when the address holds no
+/// OOGA) and the number of nonzero holders, 16 bytes LE each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HolderRank {
+    pub balance: u128,
+    pub rank: u128,
+    pub holder_count: u128,
+}
+
+impl HolderRank {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = self.balance.to_le_bytes().to_vec();
+        data.extend_from_slice(&self.rank.to_le_bytes());
+        data.extend_from_slice(&self.holder_count.to_le_bytes());
+        data
+    }
+}
+
+// Index of every address currently holding OOGA or BOOGA
+impl OogaBoogaContract {
+    // Storage keys
+    pub fn holder_count_key(&self) -> String {
+        "/holder-count".to_string()
+    }
+
+    pub fn holder_key(&self, index: u128) -> String {
+        format!("/holders/{}", index)
+    }
+
+    /// Position of the address in the index plus one (0 = not indexed)
+    pub fn holder_position_key(&self, address: &str) -> String {
+        format!("/holder-index/{}", address)
+    }
+
+    // Getters
+    pub fn holder_count(&self) -> u128 {
+        self.read_u128(&self.holder_count_key())
+    }
+
+    pub fn holder_at(&self, index: u128) -> String {
+        String::from_utf8_lossy(&self.read_bytes(&self.holder_key(index))).to_string()
+    }
+
+    pub fn is_holder(&self, address: &str) -> bool {
+        self.read_u128(&self.holder_position_key(address)) != 0
+    }
+
+    /// Addresses in the index from `start`, at most `limit` of them
+    pub fn holders(&self, start: u128, limit: u128) -> Vec<String> {
+        let end = self.holder_count().min(start.saturating_add(limit));
+        let mut holders = Vec::new();
+        let mut index = start;
+        while index < end {
+            holders.push(self.holder_at(index));
+            index += 1;
+        }
+        holders
+    }
+
+    /// Rank of the address by OOGA balance, computed by scanning the holder
+    /// index. Equal balances are ordered by position in the index.
+    pub fn holder_rank(&self, address: &str) -> Result<HolderRank> {
+        let count = self.holder_count();
+        if count > RANK_SCAN_LIMIT {
+            return Err(anyhow!("holder index exceeds rank scan limit of {}", RANK_SCAN_LIMIT));
+        }
+
+        let balance = self.ooga_balance_of(address);
+        let position = self.read_u128(&self.holder_position_key(address));
+        let mut ahead = 0;
+        let mut holder_count = 0;
+        for (index, holder) in self.holders(0, count).iter().enumerate() {
+            let other = self.ooga_balance_of(holder);
+            if other == 0 {
+                continue;
+            }
+            holder_count += 1;
+            if other > balance || (other == balance && (index as u128) + 1 < position) {
+                ahead += 1;
+            }
+        }
+
+        Ok(HolderRank {
+            balance,
+            rank: if balance == 0 { 0 } else { ahead + 1 },
+            holder_count,
+        })
+    }
+
+    // Setters
+    /// Add the address to the index if it is not there yet
+    pub(crate) fn register_holder(&self, address: &str) {
+        if self.is_holder(address) {
+            return;
+        }
+        let index = self.holder_count();
+        self.write_bytes(&self.holder_key(index), address.as_bytes().to_vec());
+        self.write_u128(&self.holder_position_key(address), index + 1);
+        self.write_u128(&self.holder_count_key(), index + 1);
+    }
+
+    /// Remove the address from the index by moving the last entry into its slot
+    pub(crate) fn unregister_holder(&self, address: &str) {
+        let position = self.read_u128(&self.holder_position_key(address));
+        if position == 0 {
+            return;
+        }
+        let last = self.holder_count() - 1;
+        if position - 1 != last {
+            let moved = self.holder_at(last);
+            self.write_bytes(&self.holder_key(position - 1), moved.as_bytes().to_vec());
+            self.write_u128(&self.holder_position_key(&moved), position);
+        }
+        self.remove(&self.holder_key(last));
+        self.remove(&self.holder_position_key(address));
+        self.write_u128(&self.holder_count_key(), last);
+    }
+}
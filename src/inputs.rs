@@ -0,0 +1,81 @@
+use anyhow::Result;
+
+use crate::Opcode;
+
+/// Largest page a paginated query returns in one call
+pub const MAX_PAGE_SIZE: u128 = 100;
+
+/// Error returned when an amount that must be nonzero is zero
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZeroAmount;
+
+impl std::fmt::Display for ZeroAmount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "amount must be nonzero")
+    }
+}
+
+impl std::error::Error for ZeroAmount {}
+
+/// Error returned for an index that does not fit in a u64
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexOutOfRange(pub u128);
+
+impl std::fmt::Display for IndexOutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "index {} out of range", self.0)
+    }
+}
+
+impl std::error::Error for IndexOutOfRange {}
+
+/// Error returned for a page size outside 1..=max
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitOutOfRange {
+    pub limit: u128,
+    pub max: u128,
+}
+
+impl std::fmt::Display for LimitOutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "limit {} out of range 1..={}", self.limit, self.max)
+    }
+}
+
+impl std::error::Error for LimitOutOfRange {}
+
+// Range checks for raw input values. Both runtimes decode every input to a
+// u128 first and pass it through these, so the same value is accepted or
+// rejected the same way whether it arrived as a u128 or a string.
+
+/// Opcode for a raw opcode number; anything without a table entry is
+/// `UnrecognizedOpcode`, however large
+pub fn decode_opcode(value: u128) -> Result<Opcode> {
+    Opcode::from_code(value)
+}
+
+pub fn decode_amount_nonzero(value: u128) -> Result<u128> {
+    if value == 0 {
+        return Err(ZeroAmount.into());
+    }
+    Ok(value)
+}
+
+/// Position in a list such as the event log or the holder list. Bounding it
+/// to a u64 keeps `index + limit` far from overflowing.
+pub fn decode_index(value: u128) -> Result<u64> {
+    u64::try_from(value).map_err(|_| IndexOutOfRange(value).into())
+}
+
+pub fn decode_limit(value: u128, max: u128) -> Result<u128> {
+    if value == 0 || value > max {
+        return Err(LimitOutOfRange { limit: value, max }.into());
+    }
+    Ok(value)
+}
+
+/// Whether an error came from one of the range checks, which keep their own
+/// error codes instead of being reported as `InvalidInput`
+pub fn is_range_error(error: &anyhow::Error) -> bool {
+    error.is::<ZeroAmount>() || error.is::<IndexOutOfRange>() || error.is::<LimitOutOfRange>()
+}
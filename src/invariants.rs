@@ -0,0 +1,88 @@
+use anyhow::{Result, anyhow};
+
+use crate::{OogaBoogaContract, Token};
+
+/// Result of comparing the summed holder balances with the stored totals
+///
+/// Encoded as the OOGA sum, BOOGA sum, total OOGA, total BOOGA, next holder
+/// index and holder count (16 bytes LE each), followed by a pass flag byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvariantReport {
+    pub ooga_sum: u128,
+    pub booga_sum: u128,
+    pub total_ooga: u128,
+    pub total_booga: u128,
+    /// Holder index at which the next page should start
+    pub next_index: u128,
+    pub holder_count: u128,
+}
+
+impl InvariantReport {
+    /// Whether every holder has been visited
+    pub fn is_complete(&self) -> bool {
+        self.next_index >= self.holder_count
+    }
+
+    /// The walk is complete and both sums match the stored totals
+    pub fn passed(&self) -> bool {
+        self.is_complete() && self.ooga_sum == self.total_ooga && self.booga_sum == self.total_booga
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(97);
+        for value in [self.ooga_sum, self.booga_sum, self.total_ooga, self.total_booga, self.next_index, self.holder_count] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.push(self.passed() as u8);
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != 97 {
+            return Err(anyhow!("invalid invariant report length"));
+        }
+        let mut values = [0u128; 6];
+        for (i, value) in values.iter_mut().enumerate() {
+            let mut bytes = [0u8; 16];
+            bytes.copy_from_slice(&data[i * 16..(i + 1) * 16]);
+            *value = u128::from_le_bytes(bytes);
+        }
+        Ok(InvariantReport {
+            ooga_sum: values[0],
+            booga_sum: values[1],
+            total_ooga: values[2],
+            total_booga: values[3],
+            next_index: values[4],
+            holder_count: values[5],
+        })
+    }
+}
+
+// Bookkeeping self-checks
+impl OogaBoogaContract {
+    /// Walk every known holder and compare the balance sums with the totals
+    pub fn check_invariants(&self) -> InvariantReport {
+        self.check_invariants_page(0, u128::MAX, 0, 0)
+    }
+
+    /// Check one page of holders, adding to the sums carried over from the
+    /// previous pages. Sums saturate so a corrupted ledger cannot trap the check.
+    pub fn check_invariants_page(&self, start: u128, limit: u128, ooga_carry: u128, booga_carry: u128) -> InvariantReport {
+        let holders = self.holders(start, limit);
+        let mut ooga_sum = ooga_carry;
+        let mut booga_sum = booga_carry;
+        for holder in &holders {
+            ooga_sum = ooga_sum.saturating_add(self.balance_of(Token::Ooga, holder));
+            booga_sum = booga_sum.saturating_add(self.balance_of(Token::Booga, holder));
+        }
+
+        InvariantReport {
+            ooga_sum,
+            booga_sum,
+            total_ooga: self.total(Token::Ooga),
+            total_booga: self.total(Token::Booga),
+            next_index: start.saturating_add(holders.len() as u128),
+            holder_count: self.holder_count(),
+        }
+    }
+}
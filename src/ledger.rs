@@ -0,0 +1,228 @@
+use anyhow::{Result, anyhow};
+use std::collections::BTreeSet;
+
+use crate::events::{Event, EventKind};
+use crate::OogaBoogaContract;
+
+/// Tokens tracked by the contract ledger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Ooga,
+    Booga,
+}
+
+impl Token {
+    /// Numeric code used for the token in opcode inputs and event records
+    pub fn code(&self) -> u8 {
+        match self {
+            Token::Ooga => 0,
+            Token::Booga => 1,
+        }
+    }
+
+    pub fn from_code(code: u128) -> Result<Self> {
+        match code {
+            0 => Ok(Token::Ooga),
+            1 => Ok(Token::Booga),
+            _ => Err(anyhow!("unknown token")),
+        }
+    }
+
+    /// The token exchanged for or into this one
+    pub fn other(&self) -> Token {
+        match self {
+            Token::Ooga => Token::Booga,
+            Token::Booga => Token::Ooga,
+        }
+    }
+
+    /// Storage key segment for the token
+    pub fn key(&self) -> &'static str {
+        match self {
+            Token::Ooga => "ooga",
+            Token::Booga => "booga",
+        }
+    }
+
+    /// Display name used in error messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            Token::Ooga => "OOGA",
+            Token::Booga => "BOOGA",
+        }
+    }
+}
+
+// Ledger storage shared by both tokens
+impl OogaBoogaContract {
+    // Storage keys
+    pub fn balance_key(&self, token: Token, address: &str) -> String {
+        format!("/{}-balance/{}", token.key(), address)
+    }
+
+    pub fn total_key(&self, token: Token) -> String {
+        format!("/total-{}", token.key())
+    }
+
+    pub fn allowance_key(&self, token: Token, owner: &str, spender: &str) -> String {
+        format!("/{}-allowance/{}/{}", token.key(), owner, spender)
+    }
+
+    // Getters
+    pub fn balance_of(&self, token: Token, address: &str) -> u128 {
+        self.read_u128(&self.balance_key(token, address))
+    }
+
+    pub fn total(&self, token: Token) -> u128 {
+        self.read_u128(&self.total_key(token))
+    }
+
+    pub fn allowance(&self, token: Token, owner: &str, spender: &str) -> u128 {
+        self.read_u128(&self.allowance_key(token, owner, spender))
+    }
+
+    /// Amount the spender can actually move right now: the allowance capped by the owner's balance
+    pub fn spendable(&self, token: Token, owner: &str, spender: &str) -> u128 {
+        self.allowance(token, owner, spender).min(self.balance_of(token, owner))
+    }
+
+    // Setters
+    /// Zero balances are deleted rather than stored, and an address left
+    /// holding neither token is dropped from the holder index
+    pub fn set_balance(&self, token: Token, address: &str, amount: u128) {
+        self.store_balance(token, address, amount);
+        if amount > 0 {
+            self.register_holder(address);
+        } else if self.balance_of(token.other(), address) == 0 {
+            self.unregister_holder(address);
+        }
+    }
+
+    /// Write a balance without updating the holder index, for callers that
+    /// know the address's place in the index does not change
+    pub(crate) fn store_balance(&self, token: Token, address: &str, amount: u128) {
+        if amount > 0 {
+            self.write_u128(&self.balance_key(token, address), amount);
+        } else {
+            self.remove(&self.balance_key(token, address));
+        }
+    }
+
+    pub fn set_total(&self, token: Token, amount: u128) {
+        self.write_u128(&self.total_key(token), amount);
+    }
+
+    pub fn set_allowance(&self, token: Token, owner: &str, spender: &str, amount: u128) {
+        self.write_u128(&self.allowance_key(token, owner, spender), amount);
+    }
+
+    // Allowance operations
+    pub(crate) fn approve(&self, token: Token, owner: &str, spender: &str, amount: u128, height: u64) -> Result<()> {
+        self.set_allowance(token, owner, spender, amount);
+        self.mark_active(owner, height);
+        Ok(())
+    }
+
+    pub(crate) fn transfer_from(&self, token: Token, spender: &str, owner: &str, recipient: &str, amount: u128, height: u64) -> Result<()> {
+        let allowance = self.allowance(token, owner, spender);
+        if allowance < amount {
+            return Err(anyhow!("insufficient {} allowance", token.name()));
+        }
+
+        let owner_balance = self.balance_of(token, owner);
+        if owner_balance < amount {
+            return Err(anyhow!("insufficient {} balance", token.name()));
+        }
+
+        // Compute the recipient balance before writing so an overflow leaves no partial update
+        let recipient_balance = if recipient == owner {
+            owner_balance - amount
+        } else {
+            self.balance_of(token, recipient)
+        };
+        let new_recipient_balance = recipient_balance.checked_add(amount)
+            .ok_or_else(|| anyhow!("balance overflow"))?;
+
+        self.set_allowance(token, owner, spender, allowance - amount);
+        self.set_balance(token, owner, owner_balance - amount);
+        self.set_balance(token, recipient, new_recipient_balance);
+        for address in [spender, owner, recipient] {
+            self.mark_active(address, height);
+        }
+
+        Ok(())
+    }
+
+    /// Pay several recipients from the sender's balance in one operation.
+    /// Duplicate recipients accumulate. Returns the number of distinct
+    /// recipients paid and the sender's remaining balance.
+    pub(crate) fn transfer_many(&self, token: Token, sender: &str, payments: &[(String, u128)], height: u64) -> Result<(u128, u128)> {
+        if payments.is_empty() {
+            return Err(anyhow!("no recipients"));
+        }
+
+        // Check the grand total against the balance before writing anything
+        let mut grand_total: u128 = 0;
+        for (_, amount) in payments {
+            grand_total = grand_total.checked_add(*amount)
+                .ok_or_else(|| anyhow!("transfer total overflow"))?;
+        }
+        let sender_balance = self.balance_of(token, sender);
+        if sender_balance < grand_total {
+            return Err(anyhow!("insufficient {} balance", token.name()));
+        }
+
+        // Credits read the staged balances, so duplicates and the sender
+        // itself accumulate; an overflow fails the operation and discards the batch
+        self.set_balance(token, sender, sender_balance - grand_total);
+        let mut recipients = BTreeSet::new();
+        for (recipient, amount) in payments {
+            let new_balance = self.balance_of(token, recipient).checked_add(*amount)
+                .ok_or_else(|| anyhow!("balance overflow"))?;
+            self.set_balance(token, recipient, new_balance);
+            self.mark_active(recipient, height);
+            recipients.insert(recipient.as_str());
+        }
+        self.mark_active(sender, height);
+
+        Ok((recipients.len() as u128, self.balance_of(token, sender)))
+    }
+
+    // Support operations
+    /// Force a balance to an explicit value, moving the total supply by the
+    /// same delta and recording the change in the event log
+    pub(crate) fn adjust_balance(&self, caller: &str, token: Token, address: &str, new_value: u128, reason: u128, height: u64) -> Result<u128> {
+        self.require_owner(caller)?;
+
+        // Validate the new total before writing anything
+        let old_value = self.balance_of(token, address);
+        let total = self.total(token);
+        let new_total = if new_value >= old_value {
+            let new_total = total.checked_add(new_value - old_value)
+                .ok_or_else(|| anyhow!("total {} supply overflow", token.name()))?;
+            // Raising a balance mints, so OOGA stays under the supply cap
+            if token == Token::Ooga && new_value > old_value {
+                self.check_ooga_supply(new_total)?;
+            }
+            new_total
+        } else {
+            total.checked_sub(old_value - new_value)
+                .ok_or_else(|| anyhow!("total {} supply underflow", token.name()))?
+        };
+
+        self.set_balance(token, address, new_value);
+        self.set_total(token, new_total);
+        self.mark_active(address, height);
+
+        // No-op adjustments are still recorded
+        Ok(self.append_event(&Event {
+            kind: EventKind::BalanceAdjusted,
+            token,
+            address: address.to_string(),
+            old_value,
+            new_value,
+            height,
+            reason,
+        }))
+    }
+}
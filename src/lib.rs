@@ -1,11 +1,46 @@
 use anyhow::{Result, anyhow};
+use std::cell::RefCell;
 
-// Include the test modules
-#[cfg(test)]
+// Include the test modules. They run on the mock runtime, which the
+// `alkanes` feature replaces, so none of them are built alongside it.
+#[cfg(all(test, not(feature = "alkanes")))]
 mod tests;
-#[cfg(test)]
+#[cfg(all(test, not(feature = "alkanes")))]
+mod metrics_tests;
+#[cfg(all(any(test, feature = "cli"), not(feature = "alkanes")))]
 pub mod test_utils;
 
+pub mod activity;
+pub mod bootstrap;
+pub mod dispatch;
+pub mod events;
+pub mod exchange;
+pub mod holders;
+pub mod inputs;
+pub mod invariants;
+pub mod ledger;
+pub mod payments;
+pub mod referrals;
+pub mod response;
+pub mod rewards;
+pub mod storage;
+pub mod swaps;
+pub mod views;
+pub use activity::Activity;
+pub use dispatch::Opcode;
+pub use events::{Event, EventKind};
+pub use exchange::{ExchangeBalances, ExchangePlan};
+pub use holders::HolderRank;
+pub use inputs::MAX_PAGE_SIZE;
+pub use invariants::InvariantReport;
+pub use ledger::Token;
+pub use payments::ClaimPayment;
+pub use response::{ErrorCode, ErrorFrame};
+pub use rewards::RewardSchedule;
+pub use storage::{PointerStorage, Storage, WriteBatch};
+pub use swaps::SwapOffer;
+pub use views::{AccountView, SupplyView};
+
 // Use Alkanes dependencies when the "alkanes" feature is enabled
 #[cfg(feature = "alkanes")]
 use alkanes_runtime::runtime::AlkaneResponder;
@@ -14,171 +49,565 @@ use alkanes_support::response::CallResponse;
 #[cfg(feature = "alkanes")]
 use metashrew_support::compat::{to_arraybuffer_layout, to_ptr};
 #[cfg(feature = "alkanes")]
-use alkanes_runtime::storage::StoragePointer;
-#[cfg(feature = "alkanes")]
 use alkanes_support::utils::shift_or_err;
 #[cfg(feature = "alkanes")]
-use metashrew_support::index_pointer::KeyValuePointer;
+use alkanes_support::parcel::{AlkaneTransfer, AlkaneTransferParcel};
+#[cfg(feature = "alkanes")]
+use dispatch::{alkane_address, Call, dispatch};
+#[cfg(all(feature = "alkanes", not(feature = "legacy-responses")))]
+use response::encode_response;
+#[cfg(all(feature = "alkanes", feature = "legacy-responses"))]
+use response::encode_legacy;
+use response::NotOwner;
+#[cfg(feature = "alkanes")]
+use response::InvalidInput;
+#[cfg(feature = "alkanes")]
+use inputs::decode_opcode;
 
-// Use test implementations when in test mode
-#[cfg(all(test, not(feature = "alkanes")))]
-use test_utils::{AlkaneResponder, CallResponse, StoragePointer};
+/// Storage layout version recorded at initialization
+pub const SCHEMA_VERSION: u128 = 1;
+
+pub struct OogaBoogaContract {
+    storage: Box<dyn Storage>,
+    // Writes staged by the operation in progress, committed together at the end
+    pending: RefCell<Option<WriteBatch>>,
+}
 
-#[derive(Default)]
-pub struct OogaBoogaContract(());
+impl Default for OogaBoogaContract {
+    fn default() -> Self {
+        Self::with_storage(Box::new(PointerStorage))
+    }
+}
+
+impl OogaBoogaContract {
+    /// Run the contract against an alternative storage backend
+    pub fn with_storage(storage: Box<dyn Storage>) -> Self {
+        OogaBoogaContract {
+            storage,
+            pending: RefCell::new(None),
+        }
+    }
+}
 
 // Storage implementation
 impl OogaBoogaContract {
-    // Storage pointers
-    pub fn ooga_balance_pointer(&self, address: &str) -> StoragePointer {
-        StoragePointer::from_keyword(&format!("/ooga-balance/{}", address))
+    // Storage keys
+    pub fn ooga_balance_key(&self, address: &str) -> String {
+        self.balance_key(Token::Ooga, address)
+    }
+
+    pub fn booga_balance_key(&self, address: &str) -> String {
+        self.balance_key(Token::Booga, address)
+    }
+
+    pub fn total_ooga_key(&self) -> String {
+        self.total_key(Token::Ooga)
+    }
+
+    pub fn total_booga_key(&self) -> String {
+        self.total_key(Token::Booga)
+    }
+
+    pub fn claim_cap_key(&self) -> String {
+        "/claim-cap-per-block".to_string()
+    }
+
+    pub fn claims_at_height_key(&self, height: u64) -> String {
+        format!("/claims-at-height/{}", height)
+    }
+
+    pub fn schema_version_key(&self) -> String {
+        "/schema-version".to_string()
+    }
+
+    pub fn owner_key(&self) -> String {
+        "/owner".to_string()
     }
 
-    pub fn booga_balance_pointer(&self, address: &str) -> StoragePointer {
-        StoragePointer::from_keyword(&format!("/booga-balance/{}", address))
+    pub fn max_supply_key(&self) -> String {
+        "/max-supply".to_string()
     }
 
-    pub fn total_ooga_pointer(&self) -> StoragePointer {
-        StoragePointer::from_keyword("/total-ooga")
+    pub fn claim_window_key(&self, bound: &str) -> String {
+        format!("/claim-window/{}", bound)
     }
 
-    pub fn total_booga_pointer(&self) -> StoragePointer {
-        StoragePointer::from_keyword("/total-booga")
+    pub fn vesting_key(&self, address: &str, field: &str) -> String {
+        format!("/vesting/{}/{}", address, field)
     }
 
     // Getters
     pub fn ooga_balance_of(&self, address: &str) -> u128 {
-        self.ooga_balance_pointer(address).get_value::<u128>()
+        self.read_u128(&self.ooga_balance_key(address))
     }
 
     pub fn booga_balance_of(&self, address: &str) -> u128 {
-        self.booga_balance_pointer(address).get_value::<u128>()
+        self.read_u128(&self.booga_balance_key(address))
     }
 
     pub fn total_ooga(&self) -> u128 {
-        self.total_ooga_pointer().get_value::<u128>()
+        self.read_u128(&self.total_ooga_key())
     }
 
     pub fn total_booga(&self) -> u128 {
-        self.total_booga_pointer().get_value::<u128>()
+        self.read_u128(&self.total_booga_key())
+    }
+
+    /// Maximum number of claims accepted in a single block (0 = unlimited)
+    pub fn claim_cap(&self) -> u128 {
+        self.read_u128(&self.claim_cap_key())
+    }
+
+    /// Number of claims already accepted at the given height
+    pub fn claims_at_height(&self, height: u64) -> u128 {
+        self.read_u128(&self.claims_at_height_key(height))
+    }
+
+    /// Schema version recorded at initialization (0 = not initialized)
+    pub fn schema_version(&self) -> u128 {
+        self.read_u128(&self.schema_version_key())
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.schema_version() != 0
+    }
+
+    /// Parameters recorded by the initialization opcode
+    pub fn init_params(&self) -> InitParams {
+        let schedule = self.reward_schedule();
+        InitParams {
+            schema_version: self.schema_version(),
+            claim_cap: self.claim_cap(),
+            referral_bonus: self.referral_bonus(),
+            max_supply: self.max_supply(),
+            claim_window: self.claim_window(),
+            base_reward: schedule.base_reward,
+            halving_interval: schedule.halving_interval,
+            owner: self.owner(),
+        }
+    }
+
+    /// Address recorded as the contract owner at initialization
+    pub fn owner(&self) -> String {
+        String::from_utf8_lossy(&self.read_bytes(&self.owner_key())).to_string()
+    }
+
+    /// Cap on the total OOGA supply (0 = uncapped)
+    pub fn max_supply(&self) -> u128 {
+        self.read_u128(&self.max_supply_key())
+    }
+
+    /// Heights between which claiming is open, as recorded at initialization
+    pub fn claim_window(&self) -> ClaimWindow {
+        ClaimWindow {
+            start: self.read_u128(&self.claim_window_key("start")) as u64,
+            end: self.read_u128(&self.claim_window_key("end")) as u64,
+        }
+    }
+
+    /// Vesting grant for an address, or `None` if no grant was created
+    pub fn vesting_grant(&self, address: &str) -> Option<VestingGrant> {
+        let total = self.read_u128(&self.vesting_key(address, "total"));
+        if total == 0 {
+            return None;
+        }
+        Some(VestingGrant {
+            total,
+            claimed: self.read_u128(&self.vesting_key(address, "claimed")),
+            start: self.read_u128(&self.vesting_key(address, "start")) as u64,
+            duration: self.read_u128(&self.vesting_key(address, "duration")) as u64,
+        })
     }
 
     // Setters
     pub fn set_ooga_balance(&self, address: &str, amount: u128) {
-        self.ooga_balance_pointer(address).set_value::<u128>(amount);
+        self.set_balance(Token::Ooga, address, amount);
     }
 
     pub fn set_booga_balance(&self, address: &str, amount: u128) {
-        self.booga_balance_pointer(address).set_value::<u128>(amount);
+        self.set_balance(Token::Booga, address, amount);
     }
 
     pub fn set_total_ooga(&self, amount: u128) {
-        self.total_ooga_pointer().set_value::<u128>(amount);
+        self.write_u128(&self.total_ooga_key(), amount);
     }
 
     pub fn set_total_booga(&self, amount: u128) {
-        self.total_booga_pointer().set_value::<u128>(amount);
+        self.write_u128(&self.total_booga_key(), amount);
+    }
+
+    pub fn set_claim_cap(&self, cap: u128) {
+        self.write_u128(&self.claim_cap_key(), cap);
+    }
+
+    pub fn set_claims_at_height(&self, height: u64, count: u128) {
+        self.write_u128(&self.claims_at_height_key(height), count);
+    }
+
+    pub fn set_schema_version(&self, version: u128) {
+        self.write_u128(&self.schema_version_key(), version);
+    }
+
+    pub fn set_owner(&self, owner: &str) {
+        self.write_bytes(&self.owner_key(), owner.as_bytes().to_vec());
+    }
+
+    pub fn set_max_supply(&self, max_supply: u128) {
+        self.write_u128(&self.max_supply_key(), max_supply);
+    }
+
+    pub fn set_claim_window(&self, window: &ClaimWindow) {
+        self.write_u128(&self.claim_window_key("start"), window.start as u128);
+        self.write_u128(&self.claim_window_key("end"), window.end as u128);
+    }
+
+    pub fn set_vesting_grant(&self, address: &str, grant: &VestingGrant) {
+        self.write_u128(&self.vesting_key(address, "total"), grant.total);
+        self.write_u128(&self.vesting_key(address, "claimed"), grant.claimed);
+        self.write_u128(&self.vesting_key(address, "start"), grant.start as u128);
+        self.write_u128(&self.vesting_key(address, "duration"), grant.duration as u128);
+    }
+
+    // Access control
+    pub(crate) fn require_owner(&self, caller: &str) -> Result<()> {
+        if self.owner() != caller {
+            return Err(NotOwner.into());
+        }
+        Ok(())
     }
 
     // Token operations
-    fn claim_ooga(&self, address: &str) -> Result<()> {
-        let current_balance = self.ooga_balance_of(address);
-        let new_balance = current_balance.checked_add(1)
+    /// Check a prospective OOGA total against the supply cap. Every path that
+    /// raises the OOGA supply goes through this.
+    pub(crate) fn check_ooga_supply(&self, new_total: u128) -> Result<()> {
+        let max_supply = self.max_supply();
+        if max_supply > 0 && new_total > max_supply {
+            return Err(anyhow!("OOGA supply cap of {} reached", max_supply));
+        }
+        Ok(())
+    }
+
+    /// Credit newly minted OOGA, enforcing the overflow checks and the supply cap
+    pub(crate) fn mint_ooga(&self, address: &str, amount: u128) -> Result<()> {
+        let new_balance = self.ooga_balance_of(address).checked_add(amount)
             .ok_or_else(|| anyhow!("balance overflow"))?;
-        
-        let total_ooga = self.total_ooga();
-        self.set_total_ooga(total_ooga + 1);
+        let new_total = self.total_ooga().checked_add(amount)
+            .ok_or_else(|| anyhow!("supply overflow"))?;
+        self.check_ooga_supply(new_total)?;
+
         self.set_ooga_balance(address, new_balance);
-        
+        self.set_total_ooga(new_total);
         Ok(())
     }
 
-    fn exchange_ooga_for_booga(&self, address: &str) -> Result<()> {
-        let ooga_balance = self.ooga_balance_of(address);
-        if ooga_balance < 1 {
-            return Err(anyhow!("insufficient OOGA balance"));
+    fn initialize_contract(&self, claim_cap: u128, referral_bonus: u128, max_supply: u128, window: ClaimWindow, schedule: RewardSchedule, owner: &str) -> Result<InitParams> {
+        if self.is_initialized() {
+            return Err(AlreadyInitialized(self.init_params()).into());
+        }
+        if window.end != 0 && window.end < window.start {
+            return Err(anyhow!("claim window ends before it starts"));
         }
 
-        let booga_balance = self.booga_balance_of(address);
-        
-        // Exchange exactly 1 OOGA for 1 BOOGA
-        self.set_ooga_balance(address, ooga_balance - 1);
-        self.set_booga_balance(address, booga_balance + 1);
+        // Initializing after a bootstrap finalizes it and keeps the loaded balances
+        if self.is_bootstrapping() {
+            self.require_owner(owner)?;
+            let total_ooga = self.total_ooga();
+            if max_supply > 0 && total_ooga > max_supply {
+                return Err(anyhow!("bootstrapped OOGA supply of {} exceeds cap of {}", total_ooga, max_supply));
+            }
+            self.set_bootstrapping(false);
+        } else {
+            self.set_total_ooga(0);
+            self.set_total_booga(0);
+        }
+        self.set_claim_cap(claim_cap);
+        self.set_referral_bonus(referral_bonus);
+        self.set_max_supply(max_supply);
+        self.set_claim_window(&window);
+        self.set_reward_schedule(&schedule);
+        self.set_owner(owner);
+        self.set_schema_version(SCHEMA_VERSION);
+
+        Ok(self.init_params())
+    }
+
+    fn claim_ooga(&self, address: &str, referrer: Option<&str>, height: u64) -> Result<()> {
+        let window = self.claim_window();
+        if !window.is_open(height) {
+            return Err(ClaimWindowClosed { start: window.start, end: window.end }.into());
+        }
+
+        // Enforce the per-block claim cap; the counter is keyed by height so
+        // it starts from zero again as soon as the chain advances
+        let claims = self.claims_at_height(height);
+        let claim_cap = self.claim_cap();
+        if claim_cap > 0 && claims >= claim_cap {
+            return Err(anyhow!("claim limit reached for block {}", height));
+        }
+
+        let reward = self.reward_schedule().current_claim_reward(height);
+        if reward == 0 {
+            return Err(anyhow!("emission ended"));
+        }
+        self.mint_ooga(address, reward)?;
+        self.mark_active(address, height);
+        if let Some(referrer) = referrer {
+            self.credit_referral(address, referrer, height)?;
+        }
+        self.set_claims_at_height(height, claims + 1);
         
-        let total_ooga = self.total_ooga();
-        let total_booga = self.total_booga();
-        self.set_total_ooga(total_ooga - 1);
-        self.set_total_booga(total_booga + 1);
+        Ok(())
+    }
+
+    fn exchange_ooga_for_booga(&self, address: &str, amount: u128, height: u64) -> Result<()> {
+        // Apply exactly what a quote for the same amount reports
+        let plan = self.quote_exchange(address, amount);
+        if let Some(failure) = plan.failure {
+            return Err(anyhow!("{}", failure));
+        }
+
+        // The address held the OOGA it is debited and is always credited some
+        // BOOGA, so it is indexed before and after and the index is left alone
+        self.store_balance(Token::Ooga, address, plan.balances.ooga_balance);
+        self.store_balance(Token::Booga, address, plan.balances.booga_balance);
+        self.set_total_ooga(plan.balances.total_ooga);
+        self.set_total_booga(plan.balances.total_booga);
+        self.mark_active(address, height);
+
+        Ok(())
+    }
+
+    fn create_vesting_grant(&self, caller: &str, beneficiary: &str, total: u128, start: u64, duration: u64, height: u64) -> Result<()> {
+        self.require_owner(caller)?;
+        if total == 0 {
+            return Err(anyhow!("vesting amount must be nonzero"));
+        }
+        // Only a single grant per address is supported
+        if self.vesting_grant(beneficiary).is_some() {
+            return Err(anyhow!("vesting grant already exists for address"));
+        }
+        // A grant that could not be minted in full on top of the current supply
+        // is rejected up front; each vested claim is checked again as it mints
+        let vested_total = self.total_ooga().checked_add(total)
+            .ok_or_else(|| anyhow!("supply overflow"))?;
+        self.check_ooga_supply(vested_total)?;
+
+        self.set_vesting_grant(beneficiary, &VestingGrant {
+            total,
+            claimed: 0,
+            start,
+            duration,
+        });
+        self.mark_active(beneficiary, height);
 
         Ok(())
     }
+
+    fn claim_vested(&self, address: &str, height: u64) -> Result<u128> {
+        let mut grant = self.vesting_grant(address)
+            .ok_or_else(|| anyhow!("no vesting grant for address"))?;
+        let claimable = grant.claimable(height);
+        if claimable == 0 {
+            return Err(anyhow!("no vested OOGA available"));
+        }
+
+        self.mint_ooga(address, claimable)?;
+        grant.claimed += claimable;
+        self.set_vesting_grant(address, &grant);
+        self.mark_active(address, height);
+
+        Ok(claimable)
+    }
 }
 
-// Contract logic implementation for Alkanes runtime
-#[cfg(feature = "alkanes")]
-impl AlkaneResponder for OogaBoogaContract {
-    fn execute(&self) -> Result<CallResponse> {
-        let context = self.context().unwrap();
-        let mut inputs = context.inputs.clone();
-        let mut response = CallResponse::forward(&context.incoming_alkanes);
+/// Parameters echoed by the initialization opcode
+///
+/// Encoded as the schema version, the per-block claim cap, the referral
+/// bonus, the OOGA supply cap, the claim window start and end heights, the
+/// base claim reward and the halving interval (16 bytes LE each), followed by
+/// the owner address bytes filling the remainder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitParams {
+    pub schema_version: u128,
+    pub claim_cap: u128,
+    pub referral_bonus: u128,
+    pub max_supply: u128,
+    pub claim_window: ClaimWindow,
+    pub base_reward: u128,
+    pub halving_interval: u64,
+    pub owner: String,
+}
 
-        // Get the opcode from the first input
-        let opcode = shift_or_err(&mut inputs)?;
-
-        match opcode {
-            // Initialize contract - opcode 0
-            0 => {
-                self.set_total_ooga(0);
-                self.set_total_booga(0);
-                Ok(response)
-            },
+impl InitParams {
+    /// Length of the fixed fields before the owner address
+    pub const HEADER_LEN: usize = 8 * 16;
 
-            // Claim OOGA - opcode 1
-            1 => {
-                let address = shift_or_err(&mut inputs)?;
-                let address_str = format!("{}", address);
-                self.claim_ooga(&address_str)?;
-                Ok(response)
-            },
+    pub fn encode(&self) -> Vec<u8> {
+        let fields = [
+            self.schema_version,
+            self.claim_cap,
+            self.referral_bonus,
+            self.max_supply,
+            self.claim_window.start as u128,
+            self.claim_window.end as u128,
+            self.base_reward,
+            self.halving_interval as u128,
+        ];
+        let mut data: Vec<u8> = fields.iter().flat_map(|field| field.to_le_bytes()).collect();
+        data.extend_from_slice(self.owner.as_bytes());
+        data
+    }
 
-            // Exchange OOGA for BOOGA - opcode 2
-            2 => {
-                let address = shift_or_err(&mut inputs)?;
-                let address_str = format!("{}", address);
-                self.exchange_ooga_for_booga(&address_str)?;
-                Ok(response)
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < Self::HEADER_LEN {
+            return Err(anyhow!("init params payload too short"));
+        }
+        let field = |index: usize| {
+            let mut bytes = [0u8; 16];
+            bytes.copy_from_slice(&data[index * 16..(index + 1) * 16]);
+            u128::from_le_bytes(bytes)
+        };
+        let owner = String::from_utf8(data[Self::HEADER_LEN..].to_vec())
+            .map_err(|_| anyhow!("init params owner is not valid UTF-8"))?;
+        Ok(InitParams {
+            schema_version: field(0),
+            claim_cap: field(1),
+            referral_bonus: field(2),
+            max_supply: field(3),
+            claim_window: ClaimWindow {
+                start: field(4) as u64,
+                end: field(5) as u64,
             },
+            base_reward: field(6),
+            halving_interval: field(7) as u64,
+            owner,
+        })
+    }
+}
 
-            // Query OOGA balance - opcode 3
-            3 => {
-                let address = shift_or_err(&mut inputs)?;
-                let address_str = format!("{}", address);
-                response.data = self.ooga_balance_of(&address_str).to_le_bytes().to_vec();
-                Ok(response)
-            },
+/// Error returned when initialization is attempted a second time, carrying
+/// the parameters recorded by the first initialization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlreadyInitialized(pub InitParams);
 
-            // Query BOOGA balance - opcode 4
-            4 => {
-                let address = shift_or_err(&mut inputs)?;
-                let address_str = format!("{}", address);
-                response.data = self.booga_balance_of(&address_str).to_le_bytes().to_vec();
-                Ok(response)
-            },
+impl std::fmt::Display for AlreadyInitialized {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "contract already initialized (schema version {}, claim cap {}, owner {})",
+            self.0.schema_version, self.0.claim_cap, self.0.owner
+        )
+    }
+}
 
-            // Query total OOGA supply - opcode 5
-            5 => {
-                response.data = self.total_ooga().to_le_bytes().to_vec();
-                Ok(response)
-            },
+impl std::error::Error for AlreadyInitialized {}
 
-            // Query total BOOGA supply - opcode 6
-            6 => {
-                response.data = self.total_booga().to_le_bytes().to_vec();
-                Ok(response)
-            },
+/// Launch window during which claims are accepted
+///
+/// Both bounds are inclusive; a zero bound leaves that side of the window
+/// unrestricted, so 0/0 keeps claiming always open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClaimWindow {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ClaimWindow {
+    pub fn is_open(&self, height: u64) -> bool {
+        height >= self.start && (self.end == 0 || height <= self.end)
+    }
+
+    /// Encoded as the start and end heights (16 bytes LE each) and an "open now" flag byte
+    pub fn encode(&self, height: u64) -> Vec<u8> {
+        let mut data = (self.start as u128).to_le_bytes().to_vec();
+        data.extend_from_slice(&(self.end as u128).to_le_bytes());
+        data.push(self.is_open(height) as u8);
+        data
+    }
+}
+
+/// Error returned for a claim outside the claim window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClaimWindowClosed {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl std::fmt::Display for ClaimWindowClosed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "claim window closed (open from {} to {})", self.start, self.end)
+    }
+}
+
+impl std::error::Error for ClaimWindowClosed {}
 
-            _ => Err(anyhow!("unrecognized opcode"))
+/// A linear OOGA vesting grant for a single beneficiary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VestingGrant {
+    pub total: u128,
+    pub claimed: u128,
+    pub start: u64,
+    pub duration: u64,
+}
+
+impl VestingGrant {
+    /// Amount unlocked at the given height, whether claimed or not
+    pub fn vested(&self, height: u64) -> u128 {
+        if height < self.start {
+            return 0;
+        }
+        let elapsed = height - self.start;
+        if elapsed >= self.duration {
+            return self.total;
+        }
+        // Split the multiplication so total * elapsed cannot overflow
+        let duration = self.duration as u128;
+        let elapsed = elapsed as u128;
+        (self.total / duration) * elapsed + (self.total % duration) * elapsed / duration
+    }
+
+    /// Amount unlocked at the given height that has not been claimed yet
+    pub fn claimable(&self, height: u64) -> u128 {
+        self.vested(height).saturating_sub(self.claimed)
+    }
+}
+
+// Contract logic implementation for Alkanes runtime
+#[cfg(feature = "alkanes")]
+impl AlkaneResponder for OogaBoogaContract {
+    fn execute(&self) -> Result<CallResponse> {
+        let context = self.context().unwrap();
+        let mut inputs = context.inputs.clone();
+
+        // Get the opcode from the first input
+        let result = shift_or_err(&mut inputs)
+            .map_err(|error| anyhow::Error::from(InvalidInput(error.to_string())))
+            .and_then(decode_opcode)
+            .and_then(|opcode| {
+                let mut call = Call {
+                    inputs,
+                    caller: alkane_address(&context.caller),
+                    height: self.height(),
+                    incoming_alkanes: context.incoming_alkanes.0.iter()
+                        .map(|transfer| (transfer.id, transfer.value))
+                        .collect(),
+                };
+                // Stage every write so a failed opcode leaves no partial state behind
+                self.atomically(|| dispatch(self, opcode, &mut call))
+            });
+        #[cfg(not(feature = "legacy-responses"))]
+        let output = encode_response(result);
+        #[cfg(feature = "legacy-responses")]
+        let output = encode_legacy(result)?;
+
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = output.data;
+        if let Some(alkanes) = output.alkanes {
+            response.alkanes = AlkaneTransferParcel(alkanes.into_iter()
+                .map(|(id, value)| AlkaneTransfer { id, value })
+                .collect());
         }
+        Ok(response)
     }
 }
 
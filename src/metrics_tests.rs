@@ -0,0 +1,242 @@
+use crate::test_utils::*;
+
+// Upper bounds on the storage traffic of each opcode. These keep the contract
+// within the Alkanes fuel budget: raise a bound only when the extra storage
+// work is intentional. Mutating opcodes also write one last-active height per
+// address involved, and read the bootstrap flag before running.
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod metrics_tests {
+    use super::*;
+    use crate::holders::RANK_SCAN_LIMIT;
+    use crate::storage::{PointerStorage, Storage};
+
+    fn probe(harness: &TestHarness, opcode: u128, inputs: Vec<&str>) -> OpMetrics {
+        let inputs = inputs.into_iter().map(|input| input.to_string()).collect();
+        let result = harness.execute(opcode, inputs);
+        assert!(result.is_ok(), "opcode {} failed: {:?}", opcode, result.err());
+        harness.last_op_metrics()
+    }
+
+    fn assert_within(metrics: OpMetrics, max_reads: usize, max_writes: usize) {
+        assert!(metrics.reads <= max_reads, "{} reads exceed bound {}: {:?}", metrics.reads, max_reads, metrics);
+        assert!(metrics.writes <= max_writes, "{} writes exceed bound {}: {:?}", metrics.writes, max_writes, metrics);
+    }
+
+    // Initialized contract where alice holds 2 OOGA and 1 BOOGA and has approved bob for both
+    fn funded_harness() -> TestHarness {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        for _ in 0..3 {
+            let _ = harness.execute(1, vec!["alice".to_string()]);
+        }
+        let _ = harness.execute(2, vec!["alice".to_string()]);
+        let _ = harness.execute(8, vec!["alice".to_string(), "100".to_string(), "0".to_string(), "10".to_string()]);
+        harness.set_caller("alice");
+        let _ = harness.execute(11, vec!["bob".to_string(), "10".to_string()]);
+        let _ = harness.execute(14, vec!["bob".to_string(), "10".to_string()]);
+        harness
+    }
+
+    #[test]
+    fn test_metrics_reset_per_execute() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        let init = harness.last_op_metrics();
+        assert!(init.writes > 0);
+
+        // A query only reflects its own traffic
+        let metrics = probe(&harness, 5, vec![]);
+        assert_eq!(metrics.reads, 1);
+        assert_eq!(metrics.writes, 0);
+        assert_eq!(metrics.bytes_written, 0);
+    }
+
+    #[test]
+    fn test_metrics_count_bytes() {
+        let harness = funded_harness();
+        let metrics = probe(&harness, 3, vec!["alice"]);
+        assert_eq!(metrics.bytes_read, 16);
+
+        // Missing keys read zero bytes
+        let metrics = probe(&harness, 3, vec!["nobody"]);
+        assert_eq!(metrics.bytes_read, 0);
+
+        let metrics = probe(&harness, 1, vec!["alice"]);
+        assert_eq!(metrics.bytes_written, metrics.writes * 16);
+    }
+
+    #[test]
+    fn test_metrics_per_storage() {
+        let first = MeteredStorage::new(Box::new(PointerStorage));
+        let second = MeteredStorage::new(Box::new(PointerStorage));
+        first.set_u128("/metered", 1).unwrap();
+        assert_eq!(first.get_u128("/metered"), 1);
+
+        // Traffic through one backend never shows up in another on the same thread
+        assert_eq!(first.metrics().get(), OpMetrics { reads: 1, writes: 1, bytes_read: 16, bytes_written: 16 });
+        assert_eq!(second.metrics().get(), OpMetrics::default());
+    }
+
+    #[test]
+    fn test_initialize_bounds() {
+        let harness = TestHarness::new();
+        // Referral bonus, supply cap, claim window and reward schedule are recorded alongside the claim cap
+        assert_within(probe(&harness, 0, vec!["5"]), 5, 12);
+    }
+
+    #[test]
+    fn test_claim_bounds() {
+        let harness = funded_harness();
+        // The supply cap, claim window and claim price checks add four reads, and the
+        // reward schedule three more
+        assert_within(probe(&harness, 1, vec!["alice"]), 13, 4);
+    }
+
+    #[test]
+    fn test_referred_claim_bounds() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec!["0".to_string(), "5".to_string()]);
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+        // First referral also pins the referrer and indexes bob as a holder
+        assert_within(probe(&harness, 1, vec!["alice", "bob"]), 20, 11);
+        assert_within(probe(&harness, 1, vec!["alice", "bob"]), 19, 7);
+    }
+
+    #[test]
+    fn test_paid_claim_bounds() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        assert_within(probe(&harness, 28, vec!["2", "1", "10"]), 2, 3);
+        assert_within(probe(&harness, 29, vec![]), 3, 0);
+        // Settlement reads the price again along with the payment token
+        let token = AlkaneId { block: 2, tx: 1 };
+        let result = harness.execute_with_alkanes(1, vec!["alice".to_string()], vec![(token, 30)]);
+        assert!(result.is_ok());
+        assert_within(harness.last_op_metrics(), 17, 7);
+    }
+
+    #[test]
+    fn test_exchange_bounds() {
+        let harness = funded_harness();
+        // Both balances and both totals, read and written once each, plus the
+        // bootstrap flag read and the last-active write every mutating opcode adds.
+        // The address keeps its place in the holder index, so it is not consulted.
+        assert_within(probe(&harness, 2, vec!["alice"]), 4 + 1, 4 + 1);
+    }
+
+    #[test]
+    fn test_query_bounds() {
+        let harness = funded_harness();
+        assert_within(probe(&harness, 3, vec!["alice"]), 1, 0);
+        assert_within(probe(&harness, 4, vec!["alice"]), 1, 0);
+        assert_within(probe(&harness, 5, vec![]), 1, 0);
+        assert_within(probe(&harness, 6, vec![]), 1, 0);
+        assert_within(probe(&harness, 7, vec![]), 2, 0);
+        assert_within(probe(&harness, 10, vec!["alice"]), 4, 0);
+        assert_within(probe(&harness, 12, vec!["alice", "bob"]), 1, 0);
+        assert_within(probe(&harness, 15, vec!["alice", "bob"]), 1, 0);
+        assert_within(probe(&harness, 17, vec!["alice", "bob"]), 2, 0);
+        assert_within(probe(&harness, 18, vec!["alice", "bob"]), 2, 0);
+        assert_within(probe(&harness, 23, vec!["alice"]), 1, 0);
+        assert_within(probe(&harness, 24, vec!["alice"]), 1, 0);
+        assert_within(probe(&harness, 25, vec![]), 2, 0);
+        assert_within(probe(&harness, 30, vec!["alice"]), 1, 0);
+        assert_within(probe(&harness, 32, vec!["alice", "1"]), 4, 0);
+        assert_within(probe(&harness, 33, vec![]), 3, 0);
+        // Linear in the holder index: fixed reads, then an address and balance per holder
+        assert_within(probe(&harness, 27, vec!["alice"]), 6, 0);
+    }
+
+    #[test]
+    fn test_holder_rank_bounds() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        for index in 0..RANK_SCAN_LIMIT {
+            let _ = harness.execute(1, vec![format!("holder{}", index)]);
+        }
+        // A full scan at the ceiling: the count, the queried balance and position,
+        // the count again for the page, then an address and a balance per holder
+        assert_within(probe(&harness, 27, vec!["holder0"]), 4 + 2 * RANK_SCAN_LIMIT as usize, 0);
+
+        // One holder past the ceiling and the query refuses to scan
+        let _ = harness.execute(1, vec!["extra".to_string()]);
+        let result = harness.execute(27, vec!["holder0".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("holder index exceeds rank scan limit of 100"));
+        }
+        assert_within(harness.last_op_metrics(), 1, 0);
+    }
+
+    #[test]
+    fn test_vesting_bounds() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        // Creating a grant checks it against the supply cap and the current total
+        assert_within(probe(&harness, 8, vec!["alice", "100", "0", "10"]), 5, 5);
+        harness.set_height(5);
+        // First credit also adds alice to the holder index, and minting checks the supply cap
+        assert_within(probe(&harness, 9, vec!["alice"]), 10, 10);
+    }
+
+    #[test]
+    fn test_adjustment_bounds() {
+        let harness = funded_harness();
+        harness.set_caller(DEFAULT_CALLER);
+        // Each adjustment also extends the per-address event index, and raising OOGA checks the supply cap
+        assert_within(probe(&harness, 19, vec!["0", "alice", "5", "1"]), 8, 7);
+        assert_within(probe(&harness, 20, vec!["0", "10"]), 2, 0);
+        assert_within(probe(&harness, 22, vec!["alice", "0", "10"]), 3, 0);
+    }
+
+    #[test]
+    fn test_multi_transfer_bounds() {
+        let harness = funded_harness();
+        // A balance read and write per recipient plus the sender's; new recipients are also indexed,
+        // and emptying the sender's OOGA checks whether it still holds BOOGA
+        assert_within(probe(&harness, 26, vec!["carol", "1", "dave", "1"]), 8, 11);
+    }
+
+    #[test]
+    fn test_invariant_check_bounds() {
+        let harness = funded_harness();
+        // Address and both balances for the single holder, both totals and the holder count twice
+        assert_within(probe(&harness, 21, vec![]), 3 + 2 + 2, 0);
+    }
+
+    #[test]
+    fn test_allowance_bounds() {
+        let harness = funded_harness();
+        assert_within(probe(&harness, 11, vec!["carol", "1"]), 1, 2);
+        assert_within(probe(&harness, 14, vec!["carol", "1"]), 1, 2);
+        harness.set_caller("bob");
+        // First transfer to carol also adds her to the holder index
+        assert_within(probe(&harness, 13, vec!["alice", "carol", "1"]), 7, 9);
+        assert_within(probe(&harness, 16, vec!["alice", "carol", "1"]), 6, 6);
+    }
+
+    #[test]
+    fn test_bootstrap_bounds() {
+        let harness = TestHarness::new();
+        // Both balances, holder indexing and an activity write per entry, on top of the
+        // flag, owner and totals; the first batch also records the owner and the flag
+        assert_within(probe(&harness, 31, vec!["alice", "1", "1", "bob", "2", "0"]), 12, 15);
+        assert_within(probe(&harness, 31, vec!["carol", "1", "0"]), 9, 8);
+        // Finalizing keeps the loaded totals and clears the flag instead
+        assert_within(probe(&harness, 0, vec![]), 4, 11);
+    }
+
+    #[test]
+    fn test_swap_bounds() {
+        let harness = funded_harness();
+        // alice offers 1 OOGA for 1 BOOGA
+        assert_within(probe(&harness, 34, vec!["0", "1", "1", "10"]), 2, 2);
+        assert_within(probe(&harness, 36, vec!["alice"]), 1, 0);
+        harness.set_caller(DEFAULT_CALLER);
+        let _ = harness.execute(19, vec!["1".to_string(), "bob".to_string(), "1".to_string(), "0".to_string()]);
+        harness.set_caller("bob");
+        // Four balances move and the offer is consumed; emptying bob's BOOGA checks his OOGA first
+        assert_within(probe(&harness, 35, vec!["alice", "1", "1"]), 11, 10);
+    }
+}
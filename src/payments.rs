@@ -0,0 +1,105 @@
+use anyhow::{Result, anyhow};
+
+use crate::OogaBoogaContract;
+#[cfg(feature = "alkanes")]
+use alkanes_support::id::AlkaneId;
+#[cfg(all(any(test, feature = "cli"), not(feature = "alkanes")))]
+use crate::test_utils::AlkaneId;
+
+/// Outcome of settling a paid claim against the incoming alkanes
+///
+/// `refunds` holds every incoming transfer minus the retained price, in the
+/// order received, ready to be returned as the response's outgoing alkanes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClaimPayment {
+    pub retained: u128,
+    pub refunded: u128,
+    pub refunds: Vec<(AlkaneId, u128)>,
+}
+
+impl ClaimPayment {
+    /// Encoded as the retained and refunded amounts of the payment token, 16 bytes LE each
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = self.retained.to_le_bytes().to_vec();
+        data.extend_from_slice(&self.refunded.to_le_bytes());
+        data
+    }
+}
+
+// Price charged for each claim, paid in incoming alkanes
+impl OogaBoogaContract {
+    // Storage keys
+    pub fn claim_price_key(&self) -> String {
+        "/claim-price".to_string()
+    }
+
+    pub fn claim_payment_token_key(&self, field: &str) -> String {
+        format!("/claim-payment-token/{}", field)
+    }
+
+    // Getters
+    /// Price of a claim in units of the payment token (0 = claims are free)
+    pub fn claim_price(&self) -> u128 {
+        self.read_u128(&self.claim_price_key())
+    }
+
+    /// Alkane accepted as payment for claims
+    pub fn claim_payment_token(&self) -> AlkaneId {
+        AlkaneId {
+            block: self.read_u128(&self.claim_payment_token_key("block")),
+            tx: self.read_u128(&self.claim_payment_token_key("tx")),
+        }
+    }
+
+    // Setters
+    pub fn set_claim_price(&self, price: u128) {
+        self.write_u128(&self.claim_price_key(), price);
+    }
+
+    pub fn set_claim_payment_token(&self, token: &AlkaneId) {
+        self.write_u128(&self.claim_payment_token_key("block"), token.block);
+        self.write_u128(&self.claim_payment_token_key("tx"), token.tx);
+    }
+
+    // Payment operations
+    pub(crate) fn configure_claim_price(&self, caller: &str, token: AlkaneId, price: u128) -> Result<()> {
+        self.require_owner(caller)?;
+        self.set_claim_payment_token(&token);
+        self.set_claim_price(price);
+        Ok(())
+    }
+
+    /// Retain the claim price from the incoming alkanes and return the rest.
+    /// The price is taken from payment token entries in the order received,
+    /// so a surplus split across several entries is refunded from each.
+    pub(crate) fn settle_claim_payment(&self, incoming: &[(AlkaneId, u128)]) -> Result<ClaimPayment> {
+        let price = self.claim_price();
+        let token = self.claim_payment_token();
+
+        let mut owed = price;
+        let mut refunded: u128 = 0;
+        let mut refunds = Vec::new();
+        for (id, value) in incoming {
+            if *id != token {
+                refunds.push((*id, *value));
+                continue;
+            }
+            let taken = owed.min(*value);
+            owed -= taken;
+            if *value > taken {
+                refunded = refunded.checked_add(*value - taken)
+                    .ok_or_else(|| anyhow!("claim payment overflow"))?;
+                refunds.push((*id, *value - taken));
+            }
+        }
+        if owed > 0 {
+            return Err(anyhow!("insufficient claim payment: received {} of {}", price - owed, price));
+        }
+
+        Ok(ClaimPayment {
+            retained: price,
+            refunded,
+            refunds,
+        })
+    }
+}
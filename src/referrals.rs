@@ -0,0 +1,80 @@
+use anyhow::{Result, anyhow};
+
+use crate::OogaBoogaContract;
+
+// Referral bonuses credited to the referrer named in a claim
+impl OogaBoogaContract {
+    // Storage keys
+    pub fn referral_bonus_key(&self) -> String {
+        "/referral-bonus".to_string()
+    }
+
+    pub fn referrer_key(&self, address: &str) -> String {
+        format!("/referrer/{}", address)
+    }
+
+    pub fn referral_earnings_key(&self, address: &str) -> String {
+        format!("/referral-earnings/{}", address)
+    }
+
+    // Getters
+    /// OOGA credited to the referrer on each referred claim (0 = referrals disabled)
+    pub fn referral_bonus(&self) -> u128 {
+        self.read_u128(&self.referral_bonus_key())
+    }
+
+    /// Referrer recorded by the address's first referred claim, if any
+    pub fn referrer_of(&self, address: &str) -> Option<String> {
+        let referrer = self.read_bytes(&self.referrer_key(address));
+        if referrer.is_empty() {
+            return None;
+        }
+        Some(String::from_utf8_lossy(&referrer).to_string())
+    }
+
+    /// Total OOGA the address has earned from referral bonuses
+    pub fn referral_earnings(&self, address: &str) -> u128 {
+        self.read_u128(&self.referral_earnings_key(address))
+    }
+
+    // Setters
+    pub fn set_referral_bonus(&self, bonus: u128) {
+        self.write_u128(&self.referral_bonus_key(), bonus);
+    }
+
+    pub fn set_referrer(&self, address: &str, referrer: &str) {
+        self.write_bytes(&self.referrer_key(address), referrer.as_bytes().to_vec());
+    }
+
+    pub fn set_referral_earnings(&self, address: &str, amount: u128) {
+        self.write_u128(&self.referral_earnings_key(address), amount);
+    }
+
+    // Referral operations
+    /// Credit the referral bonus for a claim by `claimer`. The first referred
+    /// claim pins the referrer; naming a different one later is rejected.
+    pub(crate) fn credit_referral(&self, claimer: &str, referrer: &str, height: u64) -> Result<()> {
+        let bonus = self.referral_bonus();
+        if bonus == 0 {
+            return Ok(());
+        }
+        if referrer == claimer {
+            return Err(anyhow!("cannot refer yourself"));
+        }
+        match self.referrer_of(claimer) {
+            Some(existing) if existing != referrer => {
+                return Err(anyhow!("referrer already set to {}", existing));
+            },
+            Some(_) => {},
+            None => self.set_referrer(claimer, referrer),
+        }
+
+        self.mint_ooga(referrer, bonus)?;
+        let earnings = self.referral_earnings(referrer).checked_add(bonus)
+            .ok_or_else(|| anyhow!("referral earnings overflow"))?;
+        self.set_referral_earnings(referrer, earnings);
+        self.mark_active(referrer, height);
+
+        Ok(())
+    }
+}
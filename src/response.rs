@@ -0,0 +1,209 @@
+use anyhow::{Result, anyhow};
+
+use crate::dispatch::Output;
+use crate::inputs::{IndexOutOfRange, LimitOutOfRange, ZeroAmount};
+use crate::swaps::{SwapOfferExpired, SwapOfferMismatch};
+use crate::{AlreadyInitialized, ClaimWindowClosed};
+
+/// Status byte of a successful response
+pub const STATUS_OK: u8 = 0;
+
+// Expands the table into `ErrorCode` with one variant per row, and the
+// lookups from a status byte and from the row's snake_case name, which
+// scenario fixtures use.
+macro_rules! error_codes {
+    ($( #[$meta:meta] )* $( $status:literal => $variant:ident $name:literal, )*) => {
+        $( #[$meta] )*
+        pub enum ErrorCode {
+            $( $variant = $status, )*
+        }
+
+        impl ErrorCode {
+            pub const ALL: &'static [ErrorCode] = &[$( ErrorCode::$variant ),*];
+
+            pub fn from_status(status: u8) -> Option<Self> {
+                match status {
+                    $( $status => Some(ErrorCode::$variant), )*
+                    _ => None,
+                }
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $( ErrorCode::$variant => $name, )*
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $( $name => Some(ErrorCode::$variant), )*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    /// Status byte of a failed response
+    ///
+    /// Every response data frame starts with a status byte: `STATUS_OK`
+    /// followed by the opcode's payload, or one of these codes followed by the
+    /// UTF-8 error message. Errors that carry details append a zero byte and the
+    /// detail payload after the message: `AlreadyInitialized` appends the
+    /// recorded `InitParams`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    1 => Failed "failed",
+    2 => UnrecognizedOpcode "unrecognized_opcode",
+    3 => InvalidInput "invalid_input",
+    4 => NotOwner "not_owner",
+    5 => AlreadyInitialized "already_initialized",
+    6 => SwapOfferExpired "swap_offer_expired",
+    7 => SwapOfferMismatch "swap_offer_mismatch",
+    8 => ZeroAmount "zero_amount",
+    9 => IndexOutOfRange "index_out_of_range",
+    10 => LimitOutOfRange "limit_out_of_range",
+    11 => ClaimWindowClosed "claim_window_closed",
+}
+
+impl ErrorCode {
+    /// Code for an error raised while executing an opcode
+    pub fn of(error: &anyhow::Error) -> Self {
+        if error.is::<UnrecognizedOpcode>() {
+            ErrorCode::UnrecognizedOpcode
+        } else if error.is::<InvalidInput>() {
+            ErrorCode::InvalidInput
+        } else if error.is::<NotOwner>() {
+            ErrorCode::NotOwner
+        } else if error.is::<AlreadyInitialized>() {
+            ErrorCode::AlreadyInitialized
+        } else if error.is::<SwapOfferExpired>() {
+            ErrorCode::SwapOfferExpired
+        } else if error.is::<SwapOfferMismatch>() {
+            ErrorCode::SwapOfferMismatch
+        } else if error.is::<ZeroAmount>() {
+            ErrorCode::ZeroAmount
+        } else if error.is::<IndexOutOfRange>() {
+            ErrorCode::IndexOutOfRange
+        } else if error.is::<LimitOutOfRange>() {
+            ErrorCode::LimitOutOfRange
+        } else if error.is::<ClaimWindowClosed>() {
+            ErrorCode::ClaimWindowClosed
+        } else {
+            ErrorCode::Failed
+        }
+    }
+}
+
+/// Error returned for an opcode number with no dispatch table entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnrecognizedOpcode;
+
+impl std::fmt::Display for UnrecognizedOpcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unrecognized opcode")
+    }
+}
+
+impl std::error::Error for UnrecognizedOpcode {}
+
+/// Error returned when opcode inputs are missing or cannot be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidInput(pub String);
+
+impl std::fmt::Display for InvalidInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidInput {}
+
+/// Error returned when an owner-only opcode is called by someone else
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotOwner;
+
+impl std::fmt::Display for NotOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "caller is not the owner")
+    }
+}
+
+impl std::error::Error for NotOwner {}
+
+/// A decoded error frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorFrame {
+    pub status: u8,
+    pub message: String,
+    /// Detail payload after the message; empty for errors without details
+    pub detail: Vec<u8>,
+}
+
+impl ErrorFrame {
+    pub fn code(&self) -> Option<ErrorCode> {
+        ErrorCode::from_status(self.status)
+    }
+}
+
+impl std::fmt::Display for ErrorFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ErrorFrame {}
+
+/// Frame an opcode result. Errors become error frames, so the call itself
+/// succeeds; its staged writes have already been discarded and the incoming
+/// alkanes are forwarded back.
+pub fn encode_response(result: Result<Output>) -> Output {
+    match result {
+        Ok(output) => {
+            let mut data = vec![STATUS_OK];
+            data.extend_from_slice(&output.data);
+            Output {
+                data,
+                alkanes: output.alkanes,
+            }
+        },
+        Err(error) => {
+            let mut data = vec![ErrorCode::of(&error) as u8];
+            // The zero byte separates the message from the detail payload
+            data.extend_from_slice(error.to_string().replace('\0', "\u{fffd}").as_bytes());
+            if let Some(AlreadyInitialized(params)) = error.downcast_ref::<AlreadyInitialized>() {
+                data.push(0);
+                data.extend_from_slice(&params.encode());
+            }
+            Output {
+                data,
+                alkanes: None,
+            }
+        },
+    }
+}
+
+/// Unframed responses for callers that predate the status byte: the raw
+/// payload on success, and a failed call on error.
+pub fn encode_legacy(result: Result<Output>) -> Result<Output> {
+    result
+}
+
+/// Payload of a success frame, or the error carried by an error frame
+pub fn decode_response(data: &[u8]) -> Result<&[u8]> {
+    match data.split_first() {
+        Some((&STATUS_OK, payload)) => Ok(payload),
+        Some((&status, rest)) => {
+            let (message, detail) = match rest.iter().position(|&byte| byte == 0) {
+                Some(end) => (&rest[..end], &rest[end + 1..]),
+                None => (rest, &[][..]),
+            };
+            Err(ErrorFrame {
+                status,
+                message: String::from_utf8_lossy(message).to_string(),
+                detail: detail.to_vec(),
+            }.into())
+        },
+        None => Err(anyhow!("empty response frame")),
+    }
+}
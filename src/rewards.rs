@@ -0,0 +1,78 @@
+use crate::OogaBoogaContract;
+
+/// OOGA minted per claim, halving every `halving_interval` blocks from `start`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RewardSchedule {
+    pub base_reward: u128,
+    /// Blocks between halvings (0 = the reward never halves)
+    pub halving_interval: u64,
+    /// Height the schedule counts halvings from, recorded at initialization
+    pub start: u64,
+}
+
+impl RewardSchedule {
+    fn halvings(&self, height: u64) -> u64 {
+        if self.halving_interval == 0 {
+            return 0;
+        }
+        height.saturating_sub(self.start) / self.halving_interval
+    }
+
+    /// Reward for a claim at `height`; 0 once emission has ended
+    pub fn current_claim_reward(&self, height: u64) -> u128 {
+        let halvings = self.halvings(height);
+        if halvings >= u128::BITS as u64 {
+            return 0;
+        }
+        self.base_reward >> halvings
+    }
+
+    /// Height of the next halving after `height`, or None if the reward will
+    /// not change again
+    pub fn next_halving(&self, height: u64) -> Option<u64> {
+        if self.halving_interval == 0 || self.current_claim_reward(height) == 0 {
+            return None;
+        }
+        (self.halvings(height) + 1)
+            .checked_mul(self.halving_interval)
+            .and_then(|offset| self.start.checked_add(offset))
+    }
+
+    /// Encoded as the reward at `height` and the next halving height (0 = none), 16 bytes LE each
+    pub fn encode(&self, height: u64) -> Vec<u8> {
+        let mut data = self.current_claim_reward(height).to_le_bytes().to_vec();
+        data.extend_from_slice(&(self.next_halving(height).unwrap_or(0) as u128).to_le_bytes());
+        data
+    }
+}
+
+// Claim reward schedule configured at initialization
+impl OogaBoogaContract {
+    // Storage keys
+    pub fn reward_schedule_key(&self, field: &str) -> String {
+        format!("/reward-schedule/{}", field)
+    }
+
+    // Getters
+    pub fn reward_schedule(&self) -> RewardSchedule {
+        // Before initialization nothing is stored and claims mint 1 OOGA, as they
+        // did before the schedule existed; a stored 0 means emission has ended
+        let base_key = self.reward_schedule_key("base");
+        let base_reward = match self.read_bytes(&base_key) {
+            bytes if bytes.is_empty() => 1,
+            bytes => self.decode_stored_u128(&base_key, &bytes),
+        };
+        RewardSchedule {
+            base_reward,
+            halving_interval: self.read_u128(&self.reward_schedule_key("interval")) as u64,
+            start: self.read_u128(&self.reward_schedule_key("start")) as u64,
+        }
+    }
+
+    // Setters
+    pub fn set_reward_schedule(&self, schedule: &RewardSchedule) {
+        self.write_u128(&self.reward_schedule_key("base"), schedule.base_reward);
+        self.write_u128(&self.reward_schedule_key("interval"), schedule.halving_interval as u128);
+        self.write_u128(&self.reward_schedule_key("start"), schedule.start as u128);
+    }
+}
@@ -0,0 +1,222 @@
+use anyhow::{Result, anyhow};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::OogaBoogaContract;
+#[cfg(feature = "alkanes")]
+use alkanes_runtime::storage::StoragePointer;
+#[cfg(feature = "alkanes")]
+use metashrew_support::index_pointer::KeyValuePointer;
+#[cfg(all(any(test, feature = "cli"), not(feature = "alkanes")))]
+use crate::test_utils::StoragePointer;
+
+/// Key-value backend the contract reads and writes through
+pub trait Storage {
+    fn get_u128(&self, key: &str) -> u128;
+    fn set_u128(&self, key: &str, value: u128) -> Result<()>;
+    fn get_bytes(&self, key: &str) -> Vec<u8>;
+    fn set_bytes(&self, key: &str, value: Vec<u8>) -> Result<()>;
+    fn delete(&self, key: &str) -> Result<()>;
+
+    /// Decode a stored value read as a u128, whether it came from the backend
+    /// or from the batch being staged. Test backends override this to check
+    /// the value's length.
+    fn decode_value(&self, _key: &str, bytes: &[u8]) -> u128 {
+        decode_u128(bytes)
+    }
+
+    /// Apply every write in the batch, all-or-nothing: when a write fails,
+    /// the writes already applied are rolled back before the error is returned.
+    fn commit(&self, batch: WriteBatch) -> Result<()> {
+        let mut journal = Vec::with_capacity(batch.len());
+        for (key, value) in batch.writes {
+            let previous = self.get_bytes(&key);
+            let result = match value {
+                Some(value) => self.set_bytes(&key, value),
+                None => self.delete(&key),
+            };
+            if let Err(error) = result {
+                self.rollback(journal);
+                return Err(error);
+            }
+            journal.push((key, previous));
+        }
+        Ok(())
+    }
+
+    /// Restore the values a failed `commit` overwrote, most recent first. An
+    /// empty value restores a missing key.
+    fn rollback(&self, journal: Vec<(String, Vec<u8>)>) {
+        for (key, previous) in journal.into_iter().rev() {
+            let _ = if previous.is_empty() {
+                self.delete(&key)
+            } else {
+                self.set_bytes(&key, previous)
+            };
+        }
+    }
+}
+
+/// Standard backend: the metashrew-backed `StoragePointer` on chain, or the
+/// in-memory mock pointer in tests
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PointerStorage;
+
+impl Storage for PointerStorage {
+    fn get_u128(&self, key: &str) -> u128 {
+        StoragePointer::from_keyword(key).get_value::<u128>()
+    }
+
+    fn set_u128(&self, key: &str, value: u128) -> Result<()> {
+        StoragePointer::from_keyword(key).set_value::<u128>(value);
+        Ok(())
+    }
+
+    fn get_bytes(&self, key: &str) -> Vec<u8> {
+        StoragePointer::from_keyword(key).get().as_ref().clone()
+    }
+
+    fn set_bytes(&self, key: &str, value: Vec<u8>) -> Result<()> {
+        StoragePointer::from_keyword(key).set(Arc::new(value));
+        Ok(())
+    }
+
+    // Metashrew has no removal; an empty value reads back the same as a missing key
+    fn delete(&self, key: &str) -> Result<()> {
+        StoragePointer::from_keyword(key).set(Arc::new(Vec::new()));
+        Ok(())
+    }
+
+    // Pointer writes cannot fail, and a failed call reverts its storage changes
+    // on chain, so there is nothing to journal
+    fn commit(&self, batch: WriteBatch) -> Result<()> {
+        for (key, value) in batch.writes {
+            StoragePointer::from_keyword(&key).set(Arc::new(value.unwrap_or_default()));
+        }
+        Ok(())
+    }
+}
+
+/// Writes staged by an operation; `None` marks a deletion
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteBatch {
+    pub writes: BTreeMap<String, Option<Vec<u8>>>,
+}
+
+impl WriteBatch {
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+}
+
+/// Decode a stored u128 the way metashrew's `KeyValuePointer::get_value` does:
+/// an empty value (or missing key) reads as 0, and anything else goes through
+/// `ByteView::from_bytes`, which converts the whole slice into a `[u8; 16]`
+/// and unwraps the result. A value of any other length than 16 bytes, shorter
+/// or longer, panics, which traps the call on chain.
+pub(crate) fn decode_u128(bytes: &[u8]) -> u128 {
+    if bytes.is_empty() {
+        return 0;
+    }
+    match <[u8; 16]>::try_from(bytes) {
+        Ok(value) => u128::from_le_bytes(value),
+        Err(_) => panic!("stored value of {} bytes cannot be read as u128", bytes.len()),
+    }
+}
+
+// Records a read answered by the staged batch in the harness trace; reads
+// that reach the backend are traced by the mock storage itself. Expands to
+// nothing unless the mock runtime is built with the `trace` feature.
+#[cfg(all(feature = "trace", any(test, feature = "cli"), not(feature = "alkanes")))]
+fn trace_staged_read(key: &str, value: &[u8]) {
+    crate::test_utils::trace::record_staged_read(key, value);
+}
+
+#[cfg(not(all(feature = "trace", any(test, feature = "cli"), not(feature = "alkanes"))))]
+fn trace_staged_read(_key: &str, _value: &[u8]) {}
+
+// Storage access shared by every contract module. Reads see writes staged by
+// the operation in progress; outside of an operation writes go straight to
+// the backend.
+impl OogaBoogaContract {
+    pub(crate) fn read_u128(&self, key: &str) -> u128 {
+        match self.pending.borrow().as_ref().and_then(|batch| batch.writes.get(key)) {
+            Some(Some(value)) => {
+                trace_staged_read(key, value);
+                self.decode_stored_u128(key, value)
+            },
+            Some(None) => {
+                trace_staged_read(key, &[]);
+                0
+            },
+            None => self.storage.get_u128(key),
+        }
+    }
+
+    /// Decode a u128 the contract read as bytes, with the backend's checks
+    pub(crate) fn decode_stored_u128(&self, key: &str, bytes: &[u8]) -> u128 {
+        self.storage.decode_value(key, bytes)
+    }
+
+    pub(crate) fn read_bytes(&self, key: &str) -> Vec<u8> {
+        match self.pending.borrow().as_ref().and_then(|batch| batch.writes.get(key)) {
+            Some(Some(value)) => {
+                trace_staged_read(key, value);
+                value.clone()
+            },
+            Some(None) => {
+                trace_staged_read(key, &[]);
+                Vec::new()
+            },
+            None => self.storage.get_bytes(key),
+        }
+    }
+
+    pub(crate) fn write_u128(&self, key: &str, value: u128) {
+        match self.pending.borrow_mut().as_mut() {
+            Some(batch) => {
+                batch.writes.insert(key.to_string(), Some(value.to_le_bytes().to_vec()));
+            },
+            None => self.storage.set_u128(key, value).expect("storage write failed"),
+        }
+    }
+
+    pub(crate) fn write_bytes(&self, key: &str, value: Vec<u8>) {
+        match self.pending.borrow_mut().as_mut() {
+            Some(batch) => {
+                batch.writes.insert(key.to_string(), Some(value));
+            },
+            None => self.storage.set_bytes(key, value).expect("storage write failed"),
+        }
+    }
+
+    pub(crate) fn remove(&self, key: &str) {
+        match self.pending.borrow_mut().as_mut() {
+            Some(batch) => {
+                batch.writes.insert(key.to_string(), None);
+            },
+            None => self.storage.delete(key).expect("storage delete failed"),
+        }
+    }
+
+    /// Run an operation with its writes batched: they are committed together
+    /// if it succeeds and discarded if it fails, so no partial state is left.
+    pub(crate) fn atomically<T>(&self, operation: impl FnOnce() -> Result<T>) -> Result<T> {
+        if self.pending.borrow().is_some() {
+            return Err(anyhow!("operation already in progress"));
+        }
+        *self.pending.borrow_mut() = Some(WriteBatch::default());
+
+        let result = operation();
+        let batch = self.pending.borrow_mut().take().unwrap_or_default();
+        let value = result?;
+        if !batch.is_empty() {
+            self.storage.commit(batch)?;
+        }
+        Ok(value)
+    }
+}
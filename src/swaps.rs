@@ -0,0 +1,160 @@
+use anyhow::{Result, anyhow};
+
+use crate::inputs::decode_amount_nonzero;
+use crate::ledger::Token;
+use crate::storage::decode_u128;
+use crate::OogaBoogaContract;
+
+/// Standing offer to trade `give_amount` of `give` for `want_amount` of the other token
+///
+/// Encoded as the given token's code, the given amount, the wanted amount and
+/// the expiry height, 16 bytes LE each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapOffer {
+    pub give: Token,
+    pub give_amount: u128,
+    pub want_amount: u128,
+    /// Last height at which the offer can be taken
+    pub expiry: u64,
+}
+
+impl SwapOffer {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = (self.give.code() as u128).to_le_bytes().to_vec();
+        data.extend_from_slice(&self.give_amount.to_le_bytes());
+        data.extend_from_slice(&self.want_amount.to_le_bytes());
+        data.extend_from_slice(&(self.expiry as u128).to_le_bytes());
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != 64 {
+            return Err(anyhow!("invalid swap offer length"));
+        }
+        Ok(SwapOffer {
+            give: Token::from_code(decode_u128(&data[0..16]))?,
+            give_amount: decode_u128(&data[16..32]),
+            want_amount: decode_u128(&data[32..48]),
+            expiry: decode_u128(&data[48..64]) as u64,
+        })
+    }
+}
+
+/// Error returned when a swap names an offer whose expiry height has passed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapOfferExpired {
+    pub expiry: u64,
+}
+
+impl std::fmt::Display for SwapOfferExpired {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "swap offer expired at height {}", self.expiry)
+    }
+}
+
+impl std::error::Error for SwapOfferExpired {}
+
+/// Error returned when a swap's amounts differ from the standing offer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapOfferMismatch(pub SwapOffer);
+
+impl std::fmt::Display for SwapOfferMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "swap does not match offer of {} {} for {} {}",
+            self.0.give_amount, self.0.give.name(), self.0.want_amount, self.0.give.other().name()
+        )
+    }
+}
+
+impl std::error::Error for SwapOfferMismatch {}
+
+// Two-party swaps against a standing offer. Each address has at most one
+// offer; taking it trades both legs in one operation and consumes it.
+impl OogaBoogaContract {
+    // Storage keys
+    pub fn swap_offer_key(&self, address: &str) -> String {
+        format!("/swap-offer/{}", address)
+    }
+
+    // Getters
+    pub fn swap_offer(&self, address: &str) -> Option<SwapOffer> {
+        let data = self.read_bytes(&self.swap_offer_key(address));
+        if data.is_empty() {
+            return None;
+        }
+        Some(SwapOffer::decode(&data).expect("stored swap offer is well formed"))
+    }
+
+    // Setters
+    pub fn set_swap_offer(&self, address: &str, offer: Option<&SwapOffer>) {
+        match offer {
+            Some(offer) => self.write_bytes(&self.swap_offer_key(address), offer.encode()),
+            None => self.remove(&self.swap_offer_key(address)),
+        }
+    }
+
+    // Swap operations
+    /// Record or replace the caller's offer; a zero `give_amount` withdraws it
+    pub(crate) fn offer_swap(&self, caller: &str, offer: SwapOffer, height: u64) -> Result<()> {
+        if offer.give_amount == 0 {
+            self.set_swap_offer(caller, None);
+            self.mark_active(caller, height);
+            return Ok(());
+        }
+        decode_amount_nonzero(offer.want_amount)?;
+        if offer.expiry < height {
+            return Err(anyhow!("swap offer expiry {} is in the past", offer.expiry));
+        }
+        if self.balance_of(offer.give, caller) < offer.give_amount {
+            return Err(anyhow!("insufficient {} balance", offer.give.name()));
+        }
+
+        self.set_swap_offer(caller, Some(&offer));
+        self.mark_active(caller, height);
+        Ok(())
+    }
+
+    /// Take the counterparty's offer: the caller sends `give_amount` of the
+    /// token it wants and receives `receive_amount` of the token it gives
+    pub(crate) fn swap(&self, caller: &str, counterparty: &str, give_amount: u128, receive_amount: u128, height: u64) -> Result<()> {
+        if caller == counterparty {
+            return Err(anyhow!("cannot swap with yourself"));
+        }
+        let offer = self.swap_offer(counterparty)
+            .ok_or_else(|| anyhow!("no swap offer from {}", counterparty))?;
+        if height > offer.expiry {
+            return Err(SwapOfferExpired { expiry: offer.expiry }.into());
+        }
+        if give_amount != offer.want_amount || receive_amount != offer.give_amount {
+            return Err(SwapOfferMismatch(offer).into());
+        }
+
+        // Check both legs before writing anything
+        let (received, sent) = (offer.give, offer.give.other());
+        let caller_balance = self.balance_of(sent, caller);
+        if caller_balance < give_amount {
+            return Err(anyhow!("insufficient {} balance", sent.name()));
+        }
+        let counterparty_balance = self.balance_of(received, counterparty);
+        if counterparty_balance < receive_amount {
+            return Err(anyhow!("counterparty has insufficient {} balance", received.name()));
+        }
+        let caller_received = self.balance_of(received, caller).checked_add(receive_amount)
+            .ok_or_else(|| anyhow!("balance overflow"))?;
+        let counterparty_received = self.balance_of(sent, counterparty).checked_add(give_amount)
+            .ok_or_else(|| anyhow!("balance overflow"))?;
+
+        self.set_balance(sent, caller, caller_balance - give_amount);
+        self.set_balance(received, counterparty, counterparty_balance - receive_amount);
+        self.set_balance(received, caller, caller_received);
+        self.set_balance(sent, counterparty, counterparty_received);
+        self.set_swap_offer(counterparty, None);
+        for address in [caller, counterparty] {
+            self.mark_active(address, height);
+        }
+
+        Ok(())
+    }
+}
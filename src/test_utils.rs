@@ -1,13 +1,47 @@
+use crate::dispatch::{dispatch, Call, OpcodeInputs};
+use crate::response::InvalidInput;
+#[cfg(not(feature = "legacy-responses"))]
+use crate::response::{decode_response, encode_response};
+#[cfg(feature = "legacy-responses")]
+use crate::response::encode_legacy;
+use crate::storage::{decode_u128, PointerStorage, Storage, WriteBatch};
+use crate::inputs::decode_opcode;
 use crate::OogaBoogaContract;
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::cell::{Cell, RefCell};
+
+pub mod scenario;
+#[cfg(feature = "trace")]
+pub mod trace;
 
 // Thread-local storage for testing to avoid deadlocks
 thread_local! {
     pub static MOCK_STORAGE: RefCell<HashMap<String, Vec<u8>>> = RefCell::new(HashMap::new());
-    pub static CONTEXT: RefCell<Option<Context>> = RefCell::new(None);
+    pub static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+// Storage traffic counted by a `MeteredStorage` during a single execution
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpMetrics {
+    pub reads: usize,
+    pub writes: usize,
+    pub bytes_read: usize,
+    pub bytes_written: usize,
+}
+
+// Copy of every key currently held in the mock storage
+pub fn snapshot_storage() -> HashMap<String, Vec<u8>> {
+    MOCK_STORAGE.with(|storage| storage.borrow().clone())
+}
+
+// Replace the mock storage with previously captured entries
+pub fn restore_storage(entries: HashMap<String, Vec<u8>>) {
+    MOCK_STORAGE.with(|storage| {
+        *storage.borrow_mut() = entries;
+    });
 }
 
 // Mock implementation of AlkaneResponder trait for testing
@@ -30,32 +64,203 @@ impl StoragePointer {
         }
     }
 
+    // Same decoding as metashrew, see `decode_u128`
     pub fn get_value<T: From<u128>>(&self) -> T {
-        let result = MOCK_STORAGE.with(|storage| {
-            let storage = storage.borrow();
-            if let Some(value) = storage.get(&self.key) {
-                if value.len() >= 16 {
-                    let mut bytes = [0u8; 16];
-                    bytes.copy_from_slice(&value[0..16]);
-                    let value = u128::from_le_bytes(bytes);
-                    T::from(value)
-                } else {
-                    T::from(0)
-                }
-            } else {
-                T::from(0)
-            }
-        });
-        result
+        let value = MOCK_STORAGE.with(|storage| storage.borrow().get(&self.key).cloned().unwrap_or_default());
+        #[cfg(feature = "trace")]
+        trace::record_read(&self.key, &value);
+        T::from(decode_u128(&value))
     }
 
     pub fn set_value<T: Into<u128>>(&self, value: T) {
         MOCK_STORAGE.with(|storage| {
             let mut storage = storage.borrow_mut();
             let value: u128 = value.into();
-            storage.insert(self.key.clone(), value.to_le_bytes().to_vec());
+            let _old = storage.insert(self.key.clone(), value.to_le_bytes().to_vec());
+            #[cfg(feature = "trace")]
+            trace::record_write(&self.key, _old, Some(value.to_le_bytes().to_vec()));
         });
     }
+
+    pub fn get(&self) -> Arc<Vec<u8>> {
+        MOCK_STORAGE.with(|storage| {
+            let storage = storage.borrow();
+            let value = storage.get(&self.key).cloned().unwrap_or_default();
+            #[cfg(feature = "trace")]
+            trace::record_read(&self.key, &value);
+            Arc::new(value)
+        })
+    }
+
+    // An empty value is indistinguishable from a missing key on chain, so it removes the entry
+    pub fn set(&self, value: Arc<Vec<u8>>) {
+        MOCK_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+            let _old = if value.is_empty() {
+                storage.remove(&self.key)
+            } else {
+                storage.insert(self.key.clone(), value.as_ref().clone())
+            };
+            #[cfg(feature = "trace")]
+            trace::record_write(&self.key, _old, (!value.is_empty()).then(|| value.as_ref().clone()));
+        });
+    }
+}
+
+// Storage test double backed by the mock storage that rejects writes once its
+// budget is used up. Every write of a commit counts against the budget, so a
+// commit larger than what remains fails part-way and is rolled back.
+#[derive(Debug, Clone)]
+pub struct FailingStorage {
+    remaining_writes: Rc<Cell<usize>>,
+}
+
+impl FailingStorage {
+    pub fn new(allowed_writes: usize) -> Self {
+        FailingStorage {
+            remaining_writes: Rc::new(Cell::new(allowed_writes)),
+        }
+    }
+
+    // Shared by every clone, so a test can adjust the budget after handing the storage over
+    pub fn set_remaining_writes(&self, writes: usize) {
+        self.remaining_writes.set(writes);
+    }
+
+    pub fn remaining_writes(&self) -> usize {
+        self.remaining_writes.get()
+    }
+
+    fn consume(&self, writes: usize) -> Result<()> {
+        let remaining = self.remaining_writes.get();
+        if writes > remaining {
+            return Err(anyhow!("storage write failed"));
+        }
+        self.remaining_writes.set(remaining - writes);
+        Ok(())
+    }
+}
+
+impl Storage for FailingStorage {
+    fn get_u128(&self, key: &str) -> u128 {
+        PointerStorage.get_u128(key)
+    }
+
+    fn set_u128(&self, key: &str, value: u128) -> Result<()> {
+        self.consume(1)?;
+        PointerStorage.set_u128(key, value)
+    }
+
+    fn get_bytes(&self, key: &str) -> Vec<u8> {
+        PointerStorage.get_bytes(key)
+    }
+
+    fn set_bytes(&self, key: &str, value: Vec<u8>) -> Result<()> {
+        self.consume(1)?;
+        PointerStorage.set_bytes(key, value)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.consume(1)?;
+        PointerStorage.delete(key)
+    }
+
+    // Restores through the mock storage directly, since the budget is already spent
+    fn rollback(&self, journal: Vec<(String, Vec<u8>)>) {
+        PointerStorage.rollback(journal);
+    }
+}
+
+// Backend the harness runs its contract against: forwards to the backend
+// under test and counts the traffic that reaches it. The counters belong to
+// this instance, so harnesses never see each other's traffic.
+//
+// In strict mode a u128 read of a value that is not exactly 16 bytes panics
+// naming the key, whether the value came from storage or from the batch being
+// staged. Metashrew traps on those reads too, so strict mode changes how the
+// failure is reported, not which reads fail.
+pub struct MeteredStorage {
+    inner: Box<dyn Storage>,
+    metrics: Rc<Cell<OpMetrics>>,
+    strict: Rc<Cell<bool>>,
+}
+
+impl MeteredStorage {
+    pub fn new(inner: Box<dyn Storage>) -> Self {
+        MeteredStorage {
+            inner,
+            metrics: Rc::new(Cell::new(OpMetrics::default())),
+            strict: Rc::new(Cell::new(false)),
+        }
+    }
+
+    // Shared with the storage, which keeps counting after it moves into a contract
+    pub fn metrics(&self) -> Rc<Cell<OpMetrics>> {
+        Rc::clone(&self.metrics)
+    }
+
+    // Shared the same way, so strict mode can be switched after the move
+    pub fn strict(&self) -> Rc<Cell<bool>> {
+        Rc::clone(&self.strict)
+    }
+
+    fn record(&self, reads: usize, bytes_read: usize, writes: usize, bytes_written: usize) {
+        let mut current = self.metrics.get();
+        current.reads += reads;
+        current.bytes_read += bytes_read;
+        current.writes += writes;
+        current.bytes_written += bytes_written;
+        self.metrics.set(current);
+    }
+}
+
+impl Storage for MeteredStorage {
+    // Read as raw bytes so the length is known; every backend stores a u128
+    // as its 16 LE bytes
+    fn get_u128(&self, key: &str) -> u128 {
+        let value = self.get_bytes(key);
+        self.decode_value(key, &value)
+    }
+
+    fn set_u128(&self, key: &str, value: u128) -> Result<()> {
+        self.inner.set_u128(key, value)?;
+        self.record(0, 0, 1, 16);
+        Ok(())
+    }
+
+    fn get_bytes(&self, key: &str) -> Vec<u8> {
+        let value = self.inner.get_bytes(key);
+        self.record(1, value.len(), 0, 0);
+        value
+    }
+
+    fn set_bytes(&self, key: &str, value: Vec<u8>) -> Result<()> {
+        let len = value.len();
+        self.inner.set_bytes(key, value)?;
+        self.record(0, 0, 1, len);
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.inner.delete(key)?;
+        self.record(0, 0, 1, 0);
+        Ok(())
+    }
+
+    fn decode_value(&self, key: &str, bytes: &[u8]) -> u128 {
+        if self.strict.get() && !bytes.is_empty() && bytes.len() != 16 {
+            panic!("strict storage: {} holds {} bytes, expected 16 for a u128 read", key, bytes.len());
+        }
+        self.inner.decode_value(key, bytes)
+    }
+
+    fn commit(&self, batch: WriteBatch) -> Result<()> {
+        let writes = batch.len();
+        let bytes: usize = batch.writes.values().map(|value| value.as_ref().map_or(0, Vec::len)).sum();
+        self.inner.commit(batch)?;
+        self.record(0, 0, writes, bytes);
+        Ok(())
+    }
 }
 
 // Mock implementation of shift_or_err for testing
@@ -72,25 +277,50 @@ pub fn shift_or_err<T>(v: &mut Vec<T>) -> Result<T> {
         .ok_or_else(|| anyhow!("expected value in list but list is exhausted"))
 }
 
+// Mock inputs arrive as strings: addresses are taken verbatim and numbers parsed
+impl OpcodeInputs for Vec<String> {
+    fn is_empty(&self) -> bool {
+        self.as_slice().is_empty()
+    }
+
+    fn address(&mut self) -> Result<String> {
+        shift_or_err(self)
+    }
+
+    fn number(&mut self) -> Result<u128> {
+        shift_or_err(self)?.parse().map_err(|_| anyhow!("invalid numeric input"))
+    }
+}
+
 // Mock implementation of Context for testing
 #[derive(Clone, Debug)]
 pub struct Context {
     pub inputs: Vec<String>,
-    pub incoming_alkanes: Vec<u8>,
+    pub incoming_alkanes: Vec<(AlkaneId, u128)>,
+    pub height: u64,
+    pub caller: String,
+}
+
+// Mock implementation of AlkaneId for testing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AlkaneId {
+    pub block: u128,
+    pub tx: u128,
 }
 
-// Mock implementation of CallResponse for testing
+// Mock implementation of CallResponse for testing; outgoing alkanes are (id, amount) transfers
 #[derive(Debug)]
 pub struct CallResponse {
     pub data: Vec<u8>,
-    pub alkanes: (Vec<u8>, Vec<u8>),
+    pub alkanes: Vec<(AlkaneId, u128)>,
 }
 
 impl CallResponse {
-    pub fn forward(_incoming_alkanes: &Vec<u8>) -> Self {
+    // Like the runtime's forward, every incoming transfer is returned unless the opcode says otherwise
+    pub fn forward(incoming_alkanes: &[(AlkaneId, u128)]) -> Self {
         CallResponse {
             data: Vec::new(),
-            alkanes: (Vec::new(), Vec::new()),
+            alkanes: incoming_alkanes.to_vec(),
         }
     }
 }
@@ -110,62 +340,33 @@ impl AlkaneResponder for OogaBoogaContract {
     fn execute(&self) -> Result<CallResponse> {
         let context = self.context()?;
         let mut inputs = context.inputs.clone();
-        let mut response = CallResponse::forward(&context.incoming_alkanes);
 
         // Get the opcode from the first input
-        let opcode_str = shift_or_err(&mut inputs)?;
-        let opcode: u8 = opcode_str.parse().map_err(|_| anyhow!("invalid opcode format"))?;
-
-        match opcode {
-            // Initialize contract - opcode 0
-            0 => {
-                self.set_total_ooga(0);
-                self.set_total_booga(0);
-                Ok(response)
-            },
-
-            // Claim OOGA - opcode 1
-            1 => {
-                let address = shift_or_err(&mut inputs)?;
-                self.claim_ooga(&address)?;
-                Ok(response)
-            },
-
-            // Exchange OOGA for BOOGA - opcode 2
-            2 => {
-                let address = shift_or_err(&mut inputs)?;
-                self.exchange_ooga_for_booga(&address)?;
-                Ok(response)
-            },
-
-            // Query OOGA balance - opcode 3
-            3 => {
-                let address = shift_or_err(&mut inputs)?;
-                response.data = self.ooga_balance_of(&address).to_le_bytes().to_vec();
-                Ok(response)
-            },
-
-            // Query BOOGA balance - opcode 4
-            4 => {
-                let address = shift_or_err(&mut inputs)?;
-                response.data = self.booga_balance_of(&address).to_le_bytes().to_vec();
-                Ok(response)
-            },
-
-            // Query total OOGA supply - opcode 5
-            5 => {
-                response.data = self.total_ooga().to_le_bytes().to_vec();
-                Ok(response)
-            },
-
-            // Query total BOOGA supply - opcode 6
-            6 => {
-                response.data = self.total_booga().to_le_bytes().to_vec();
-                Ok(response)
-            },
-
-            _ => Err(anyhow!("unrecognized opcode"))
+        let result = shift_or_err(&mut inputs)
+            .and_then(|opcode| opcode.parse::<u128>().map_err(|_| anyhow!("invalid opcode format")))
+            .map_err(|error| anyhow::Error::from(InvalidInput(error.to_string())))
+            .and_then(decode_opcode)
+            .and_then(|opcode| {
+                let mut call = Call {
+                    inputs,
+                    caller: context.caller.clone(),
+                    height: context.height,
+                    incoming_alkanes: context.incoming_alkanes.clone(),
+                };
+                // Stage every write so a failed opcode leaves no partial state behind
+                self.atomically(|| dispatch(self, opcode, &mut call))
+            });
+        #[cfg(not(feature = "legacy-responses"))]
+        let output = encode_response(result);
+        #[cfg(feature = "legacy-responses")]
+        let output = encode_legacy(result)?;
+
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = output.data;
+        if let Some(alkanes) = output.alkanes {
+            response.alkanes = alkanes;
         }
+        Ok(response)
     }
     
     fn run(&self) -> Result<CallResponse> {
@@ -176,19 +377,103 @@ impl AlkaneResponder for OogaBoogaContract {
 // Test harness for executing contract operations
 pub struct TestHarness {
     pub contract: OogaBoogaContract,
+    height: Cell<u64>,
+    caller: RefCell<String>,
+    metrics: Rc<Cell<OpMetrics>>,
+    last_metrics: Cell<OpMetrics>,
+    strict: Rc<Cell<bool>>,
+    #[cfg(feature = "trace")]
+    trace: RefCell<Vec<trace::TraceEntry>>,
+}
+
+// Caller used by the harness until `set_caller` is invoked
+pub const DEFAULT_CALLER: &str = "deployer";
+
+impl Default for TestHarness {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TestHarness {
     pub fn new() -> Self {
-        let contract = OogaBoogaContract::default();
+        Self::with_storage(Box::new(PointerStorage))
+    }
+
+    // Harness whose contract runs against the given storage backend, metered
+    // and in strict mode
+    pub fn with_storage(storage: Box<dyn Storage>) -> Self {
+        let storage = MeteredStorage::new(storage);
+        let metrics = storage.metrics();
+        let strict = storage.strict();
+        strict.set(true);
+        let contract = OogaBoogaContract::with_storage(Box::new(storage));
         // Reset storage
         MOCK_STORAGE.with(|storage| {
             storage.borrow_mut().clear();
         });
-        Self { contract }
+        Self {
+            contract,
+            height: Cell::new(0),
+            caller: RefCell::new(DEFAULT_CALLER.to_string()),
+            metrics,
+            last_metrics: Cell::new(OpMetrics::default()),
+            strict,
+            #[cfg(feature = "trace")]
+            trace: RefCell::new(Vec::new()),
+        }
+    }
+
+    // Turn strict storage reads off to see how a mismatched value fails on chain
+    pub fn set_strict_storage(&self, strict: bool) {
+        self.strict.set(strict);
+    }
+
+    pub fn strict_storage(&self) -> bool {
+        self.strict.get()
+    }
+
+    // Storage traffic of the most recent `execute` call
+    pub fn last_op_metrics(&self) -> OpMetrics {
+        self.last_metrics.get()
+    }
+
+    // Trace of every execution since the last call, oldest first
+    #[cfg(feature = "trace")]
+    pub fn take_trace(&self) -> Vec<trace::TraceEntry> {
+        self.trace.take()
+    }
+
+    // Set the caller reported to the contract by subsequent executions
+    pub fn set_caller(&self, caller: &str) {
+        *self.caller.borrow_mut() = caller.to_string();
+    }
+
+    // Set the block height reported to the contract by subsequent executions
+    pub fn set_height(&self, height: u64) {
+        self.height.set(height);
     }
     
-    pub fn execute(&self, opcode: u8, inputs: Vec<String>) -> Result<CallResponse> {
+    // Execute an opcode, turning an error frame into an error
+    pub fn execute(&self, opcode: u128, inputs: Vec<String>) -> Result<CallResponse> {
+        self.execute_with_alkanes(opcode, inputs, Vec::new())
+    }
+
+    // Execute an opcode and return the response frame as is, error frames included
+    pub fn execute_framed(&self, opcode: u128, inputs: Vec<String>) -> Result<CallResponse> {
+        self.execute_framed_with_alkanes(opcode, inputs, Vec::new())
+    }
+
+    // Execute an opcode with alkanes transferred in alongside the call
+    pub fn execute_with_alkanes(&self, opcode: u128, inputs: Vec<String>, incoming_alkanes: Vec<(AlkaneId, u128)>) -> Result<CallResponse> {
+        let response = self.execute_framed_with_alkanes(opcode, inputs, incoming_alkanes)?;
+        #[cfg(not(feature = "legacy-responses"))]
+        decode_response(&response.data)?;
+        Ok(response)
+    }
+
+    // Execute an opcode with alkanes transferred in and return the response frame as is
+    pub fn execute_framed_with_alkanes(&self, opcode: u128, inputs: Vec<String>, incoming_alkanes: Vec<(AlkaneId, u128)>) -> Result<CallResponse> {
         // Create proper context with inputs
         let mut all_inputs = vec![opcode.to_string()];
         all_inputs.extend(inputs);
@@ -197,18 +482,112 @@ impl TestHarness {
         CONTEXT.with(|ctx| {
             *ctx.borrow_mut() = Some(Context {
                 inputs: all_inputs,
-                incoming_alkanes: Vec::new(),
+                incoming_alkanes,
+                height: self.height.get(),
+                caller: self.caller.borrow().clone(),
             });
         });
         
-        // Execute contract
-        self.contract.execute()
+        // Execute contract, counting only the storage traffic of this call
+        self.metrics.set(OpMetrics::default());
+        #[cfg(feature = "trace")]
+        trace::begin(opcode, &self.caller.borrow(), self.height.get());
+        let result = self.contract.execute();
+        self.last_metrics.set(self.metrics.get());
+        #[cfg(feature = "trace")]
+        self.trace.borrow_mut().extend(trace::finish(&result));
+        result
+    }
+
+    // Execute an opcode and report every mock storage key it added, removed or changed.
+    // Only meaningful when the contract runs against the mock storage.
+    pub fn execute_with_diff(&self, opcode: u128, inputs: Vec<String>) -> (Result<CallResponse>, StorageDiff) {
+        let before = snapshot_storage();
+        let result = self.execute(opcode, inputs);
+        (result, StorageDiff::between(&before, &snapshot_storage()))
     }
 }
 
+// Mock storage changes made by a single execution
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageDiff {
+    pub added: BTreeMap<String, Vec<u8>>,
+    pub removed: BTreeMap<String, Vec<u8>>,
+    // Old and new bytes of keys present both before and after
+    pub changed: BTreeMap<String, (Vec<u8>, Vec<u8>)>,
+}
+
+impl StorageDiff {
+    pub fn between(before: &HashMap<String, Vec<u8>>, after: &HashMap<String, Vec<u8>>) -> Self {
+        let mut diff = StorageDiff::default();
+        for (key, new) in after {
+            match before.get(key) {
+                None => {
+                    diff.added.insert(key.clone(), new.clone());
+                },
+                Some(old) if old != new => {
+                    diff.changed.insert(key.clone(), (old.clone(), new.clone()));
+                },
+                Some(_) => {},
+            }
+        }
+        for (key, old) in before {
+            if !after.contains_key(key) {
+                diff.removed.insert(key.clone(), old.clone());
+            }
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    // Every key added, removed or changed, in sorted order
+    pub fn touched_keys(&self) -> Vec<String> {
+        let mut keys: BTreeSet<String> = self.added.keys().cloned().collect();
+        keys.extend(self.removed.keys().cloned());
+        keys.extend(self.changed.keys().cloned());
+        keys.into_iter().collect()
+    }
+
+    // Panic unless exactly the given keys were touched
+    pub fn assert_only_touched(&self, keys: &[&str]) {
+        let expected: BTreeSet<String> = keys.iter().map(|key| key.to_string()).collect();
+        let touched: BTreeSet<String> = self.touched_keys().into_iter().collect();
+        let unexpected: Vec<&String> = touched.difference(&expected).collect();
+        let untouched: Vec<&String> = expected.difference(&touched).collect();
+        assert!(
+            unexpected.is_empty() && untouched.is_empty(),
+            "unexpected writes to {:?}, expected writes missing for {:?}; diff: {:?}",
+            unexpected, untouched, self
+        );
+    }
+
+    // New value of a key that was added or changed, decoded as a u128
+    pub fn new_u128(&self, key: &str) -> Option<u128> {
+        self.added.get(key)
+            .or_else(|| self.changed.get(key).map(|(_, new)| new))
+            .map(|bytes| decode_u128(bytes))
+    }
+}
+
+// Payload of a success frame; panics on an error frame
+pub fn payload(response: &CallResponse) -> &[u8] {
+    #[cfg(feature = "legacy-responses")]
+    return &response.data;
+    #[cfg(not(feature = "legacy-responses"))]
+    decode_response(&response.data).expect("expected a success frame")
+}
+
 // Helper function to extract u128 from response data
 pub fn extract_u128(response: &CallResponse) -> u128 {
+    extract_u128_at(response, 0)
+}
+
+// Helper function to extract the u128 at the given 16-byte slot of response data
+pub fn extract_u128_at(response: &CallResponse, index: usize) -> u128 {
     let mut bytes = [0u8; 16];
-    bytes.copy_from_slice(&response.data[0..16]);
+    bytes.copy_from_slice(&payload(response)[index * 16..(index + 1) * 16]);
     u128::from_le_bytes(bytes)
 }
@@ -0,0 +1,291 @@
+// Declarative multi-step scenarios for the test harness.
+//
+// A scenario is a JSON document:
+//
+// {
+//   "name": "complete flow",
+//   "steps": [
+//     { "opcode": 0 },
+//     { "caller": "alice", "height": 5, "opcode": 1, "inputs": ["alice"] },
+//     { "opcode": 3, "inputs": ["alice"], "payload": ["1"] },
+//     { "opcode": 2, "inputs": ["bob"], "expect": "error",
+//       "error_code": "failed", "error": "insufficient OOGA balance" }
+//   ],
+//   "balances": { "alice": { "ooga": "1", "booga": "0" } }
+// }
+//
+// Steps run in order. `caller` defaults to DEFAULT_CALLER and `height` to the
+// current harness height; `expect` is "ok" (the default) or "error".
+// `payload` is either a hex string or a list of u128 values, one 16-byte slot
+// each. `error_code` is the snake_case name of an ErrorCode and `error` a
+// substring of the error message. Amounts may be given as numbers or strings.
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+
+use super::{payload, snapshot_storage, CallResponse, StorageDiff, TestHarness, DEFAULT_CALLER};
+use crate::response::{ErrorCode, ErrorFrame};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expect {
+    Ok,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub caller: String,
+    pub height: Option<u64>,
+    pub opcode: u128,
+    pub inputs: Vec<String>,
+    pub expect: Expect,
+    pub payload: Option<Vec<u8>>,
+    pub error_code: Option<ErrorCode>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scenario {
+    pub name: String,
+    pub steps: Vec<Step>,
+    // Expected OOGA and BOOGA balances per address once every step has run
+    pub balances: Vec<(String, u128, u128)>,
+}
+
+impl Scenario {
+    pub fn parse(source: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(source)?;
+        let name = value.get("name").and_then(Value::as_str).unwrap_or("unnamed").to_string();
+
+        let steps = value.get("steps").and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("scenario has no steps list"))?
+            .iter()
+            .enumerate()
+            .map(|(index, step)| parse_step(step).map_err(|e| anyhow!("step {}: {}", index, e)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut balances = Vec::new();
+        if let Some(expected) = value.get("balances") {
+            let expected = expected.as_object().ok_or_else(|| anyhow!("balances must be an object"))?;
+            for (address, balance) in expected {
+                let ooga = balance.get("ooga").map(parse_u128).transpose()?.unwrap_or(0);
+                let booga = balance.get("booga").map(parse_u128).transpose()?.unwrap_or(0);
+                balances.push((address.clone(), ooga, booga));
+            }
+        }
+
+        Ok(Scenario { name, steps, balances })
+    }
+
+    // Parse inline JSON, or read the scenario from the file at `source`
+    pub fn load(source: &str) -> Result<Self> {
+        if source.trim_start().starts_with('{') {
+            return Self::parse(source);
+        }
+        let contents = fs::read_to_string(source)
+            .map_err(|e| anyhow!("failed to read scenario {}: {}", source, e))?;
+        Self::parse(&contents)
+    }
+}
+
+fn parse_step(step: &Value) -> Result<Step> {
+    let opcode = parse_u128(step.get("opcode").ok_or_else(|| anyhow!("missing opcode"))?)?;
+
+    let inputs = match step.get("inputs") {
+        None => Vec::new(),
+        Some(inputs) => inputs.as_array()
+            .ok_or_else(|| anyhow!("inputs must be a list"))?
+            .iter()
+            .map(|input| match input {
+                Value::String(input) => Ok(input.clone()),
+                Value::Number(_) => Ok(parse_u128(input)?.to_string()),
+                _ => Err(anyhow!("inputs must be strings or numbers")),
+            })
+            .collect::<Result<Vec<_>>>()?,
+    };
+
+    let expect = match step.get("expect").map(|expect| expect.as_str()) {
+        None | Some(Some("ok")) => Expect::Ok,
+        Some(Some("error")) => Expect::Error,
+        _ => return Err(anyhow!("expect must be \"ok\" or \"error\"")),
+    };
+
+    let payload = step.get("payload").map(|payload| match payload {
+        Value::String(hex) => decode_hex(hex),
+        Value::Array(slots) => {
+            let mut bytes = Vec::new();
+            for slot in slots {
+                bytes.extend_from_slice(&parse_u128(slot)?.to_le_bytes());
+            }
+            Ok(bytes)
+        },
+        _ => Err(anyhow!("payload must be a hex string or a list of u128 values")),
+    }).transpose()?;
+
+    let error_code = step.get("error_code")
+        .map(|code| code.as_str().and_then(ErrorCode::from_name)
+            .ok_or_else(|| anyhow!("unknown error code {:?}", code)))
+        .transpose()?;
+
+    Ok(Step {
+        caller: step.get("caller").and_then(Value::as_str).unwrap_or(DEFAULT_CALLER).to_string(),
+        height: step.get("height").and_then(Value::as_u64),
+        opcode,
+        inputs,
+        expect,
+        payload,
+        error_code,
+        error: step.get("error").and_then(Value::as_str).map(str::to_string),
+    })
+}
+
+fn parse_u128(value: &Value) -> Result<u128> {
+    let text = match value {
+        Value::String(text) => text.clone(),
+        Value::Number(number) => number.to_string(),
+        _ => return Err(anyhow!("expected an amount, got {:?}", value)),
+    };
+    text.parse().map_err(|_| anyhow!("invalid amount: {}", text))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair).ok()
+                .filter(|pair| pair.len() == 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| anyhow!("invalid hex payload: {}", hex))
+        })
+        .collect()
+}
+
+// The first step that did not behave as expected
+#[derive(Debug, Clone)]
+pub struct ScenarioFailure {
+    // Index of the failing step, or None when only the final balances are off
+    pub step: Option<usize>,
+    pub reason: String,
+    // Writes made by the failing step, or by the whole scenario for balance mismatches
+    pub diff: StorageDiff,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScenarioReport {
+    pub name: String,
+    pub steps_run: usize,
+    pub failure: Option<ScenarioFailure>,
+}
+
+impl ScenarioReport {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    // Panic with the report unless every step and balance matched
+    pub fn assert_passed(&self) {
+        assert!(self.passed(), "{}", self);
+    }
+}
+
+impl fmt::Display for ScenarioReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.failure {
+            None => write!(f, "scenario {:?} passed ({} steps)", self.name, self.steps_run),
+            Some(failure) => {
+                match failure.step {
+                    Some(step) => write!(f, "scenario {:?} failed at step {}: {}", self.name, step, failure.reason)?,
+                    None => write!(f, "scenario {:?} failed after {} steps: {}", self.name, self.steps_run, failure.reason)?,
+                }
+                write!(f, "\nstorage diff: {:?}", failure.diff)
+            },
+        }
+    }
+}
+
+// Compare one step's outcome against its expectations
+fn check_step(step: &Step, result: &Result<CallResponse>) -> Option<String> {
+    match (step.expect, result) {
+        (Expect::Ok, Err(error)) => Some(format!("expected success, got error: {}", error)),
+        (Expect::Error, Ok(response)) => Some(format!(
+            "expected an error, got success with payload {}", encode_hex(payload(response))
+        )),
+        (Expect::Ok, Ok(response)) => {
+            let actual = payload(response);
+            match &step.payload {
+                Some(expected) if expected.as_slice() != actual => Some(format!(
+                    "expected payload {}, got {}", encode_hex(expected), encode_hex(actual)
+                )),
+                _ => None,
+            }
+        },
+        (Expect::Error, Err(error)) => {
+            let code = error.downcast_ref::<ErrorFrame>()
+                .and_then(ErrorFrame::code)
+                .unwrap_or_else(|| ErrorCode::of(error));
+            let message = error.to_string();
+            if let Some(expected) = step.error_code.filter(|expected| *expected != code) {
+                return Some(format!("expected error code {:?}, got {:?}: {}", expected, code, message));
+            }
+            match &step.error {
+                Some(expected) if !message.contains(expected.as_str()) => Some(format!(
+                    "expected error containing {:?}, got {:?}", expected, message
+                )),
+                _ => None,
+            }
+        },
+    }
+}
+
+impl TestHarness {
+    // Run every step of a scenario, stopping at the first one that fails
+    pub fn run_scenario_steps(&self, scenario: &Scenario) -> ScenarioReport {
+        let start = snapshot_storage();
+        let mut report = ScenarioReport {
+            name: scenario.name.clone(),
+            steps_run: 0,
+            failure: None,
+        };
+
+        for (index, step) in scenario.steps.iter().enumerate() {
+            self.set_caller(&step.caller);
+            if let Some(height) = step.height {
+                self.set_height(height);
+            }
+            let (result, diff) = self.execute_with_diff(step.opcode, step.inputs.clone());
+            report.steps_run += 1;
+            if let Some(reason) = check_step(step, &result) {
+                report.failure = Some(ScenarioFailure { step: Some(index), reason, diff });
+                return report;
+            }
+        }
+
+        for (address, ooga, booga) in &scenario.balances {
+            let actual = (self.contract.ooga_balance_of(address), self.contract.booga_balance_of(address));
+            if actual != (*ooga, *booga) {
+                report.failure = Some(ScenarioFailure {
+                    step: None,
+                    reason: format!(
+                        "expected {} to hold {} OOGA and {} BOOGA, found {} OOGA and {} BOOGA",
+                        address, ooga, booga, actual.0, actual.1
+                    ),
+                    diff: StorageDiff::between(&start, &snapshot_storage()),
+                });
+                return report;
+            }
+        }
+
+        report
+    }
+
+    // Load a scenario from a file path or inline JSON and run it
+    pub fn run_scenario(&self, source: &str) -> Result<ScenarioReport> {
+        Ok(self.run_scenario_steps(&Scenario::load(source)?))
+    }
+}
@@ -0,0 +1,177 @@
+// Structured traces of harness executions, behind the `trace` feature.
+//
+// While the harness runs an opcode, the mock StoragePointer records every
+// read (with the value found) and every write (with the old and new value),
+// and the dispatch table records each input it decodes. Reads of values the
+// operation staged itself never reach storage; the contract's read path
+// records those, marked as staged. Writes appear once the operation commits.
+// The wasm build has no mock runtime, so none of this is compiled there.
+
+use anyhow::Result;
+use std::cell::RefCell;
+use std::fmt;
+
+use super::CallResponse;
+#[cfg(not(feature = "legacy-responses"))]
+use crate::response::decode_response;
+use crate::response::{ErrorCode, ErrorFrame};
+use crate::Opcode;
+
+thread_local! {
+    // Entry of the execution in progress, if the harness is tracing one
+    static ACTIVE: RefCell<Option<TraceEntry>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRead {
+    pub key: String,
+    // Empty when the key is missing
+    pub value: Vec<u8>,
+    // Answered by the operation's own staged writes rather than storage
+    pub staged: bool,
+}
+
+// `None` on either side means the key was missing before or removed by the write
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceWrite {
+    pub key: String,
+    pub old: Option<Vec<u8>>,
+    pub new: Option<Vec<u8>>,
+}
+
+// One harness execution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub opcode: u128,
+    pub caller: String,
+    pub height: u64,
+    // Decoded inputs as (name, Debug rendering), in decoding order
+    pub params: Vec<(String, String)>,
+    pub reads: Vec<TraceRead>,
+    pub writes: Vec<TraceWrite>,
+    // Success payload, or the error frame the call produced
+    pub result: Result<Vec<u8>, ErrorFrame>,
+}
+
+impl TraceEntry {
+    pub fn opcode_name(&self) -> Option<Opcode> {
+        Opcode::from_code(self.opcode).ok()
+    }
+}
+
+pub(super) fn begin(opcode: u128, caller: &str, height: u64) {
+    ACTIVE.with(|active| {
+        *active.borrow_mut() = Some(TraceEntry {
+            opcode,
+            caller: caller.to_string(),
+            height,
+            params: Vec::new(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            result: Ok(Vec::new()),
+        });
+    });
+}
+
+pub(super) fn finish(result: &Result<CallResponse>) -> Option<TraceEntry> {
+    let mut entry = ACTIVE.with(|active| active.borrow_mut().take())?;
+    entry.result = match result {
+        #[cfg(not(feature = "legacy-responses"))]
+        Ok(response) => decode_response(&response.data).map(<[u8]>::to_vec).map_err(|error| error_frame(&error)),
+        #[cfg(feature = "legacy-responses")]
+        Ok(response) => Ok(response.data.clone()),
+        Err(error) => Err(error_frame(error)),
+    };
+    Some(entry)
+}
+
+fn error_frame(error: &anyhow::Error) -> ErrorFrame {
+    match error.downcast_ref::<ErrorFrame>() {
+        Some(frame) => frame.clone(),
+        None => ErrorFrame {
+            status: ErrorCode::of(error) as u8,
+            message: error.to_string(),
+            detail: Vec::new(),
+        },
+    }
+}
+
+fn record(update: impl FnOnce(&mut TraceEntry)) {
+    ACTIVE.with(|active| {
+        if let Some(entry) = active.borrow_mut().as_mut() {
+            update(entry);
+        }
+    });
+}
+
+pub(crate) fn record_param(name: &str, value: String) {
+    record(|entry| entry.params.push((name.to_string(), value)));
+}
+
+pub(super) fn record_read(key: &str, value: &[u8]) {
+    record(|entry| entry.reads.push(TraceRead {
+        key: key.to_string(),
+        value: value.to_vec(),
+        staged: false,
+    }));
+}
+
+pub(crate) fn record_staged_read(key: &str, value: &[u8]) {
+    record(|entry| entry.reads.push(TraceRead {
+        key: key.to_string(),
+        value: value.to_vec(),
+        staged: true,
+    }));
+}
+
+pub(super) fn record_write(key: &str, old: Option<Vec<u8>>, new: Option<Vec<u8>>) {
+    record(|entry| entry.writes.push(TraceWrite {
+        key: key.to_string(),
+        old,
+        new,
+    }));
+}
+
+// Hex, followed by the decoded amount for 16-byte values
+fn describe(value: Option<&[u8]>) -> String {
+    match value {
+        None => "<none>".to_string(),
+        Some([]) => "<empty>".to_string(),
+        Some(bytes) => {
+            let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            match <[u8; 16]>::try_from(bytes) {
+                Ok(amount) => format!("0x{} ({})", hex, u128::from_le_bytes(amount)),
+                Err(_) => format!("0x{}", hex),
+            }
+        },
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.opcode_name() {
+            Some(name) => write!(f, "opcode {} ({:?})", self.opcode, name)?,
+            None => write!(f, "opcode {} (unrecognized)", self.opcode)?,
+        }
+        writeln!(f, " by {} at height {}", self.caller, self.height)?;
+        for (name, value) in &self.params {
+            writeln!(f, "  param {} = {}", name, value)?;
+        }
+        for read in &self.reads {
+            let staged = if read.staged { " (staged)" } else { "" };
+            writeln!(f, "  read  {} = {}{}", read.key, describe(Some(&read.value)), staged)?;
+        }
+        for write in &self.writes {
+            writeln!(f, "  write {}: {} -> {}", write.key, describe(write.old.as_deref()), describe(write.new.as_deref()))?;
+        }
+        match &self.result {
+            Ok(payload) => writeln!(f, "  ok {}", describe(Some(payload))),
+            Err(frame) => writeln!(f, "  error {:?}: {}", frame.code(), frame.message),
+        }
+    }
+}
+
+// Every entry of a trace, numbered, for reading in test output
+pub fn pretty_trace(entries: &[TraceEntry]) -> String {
+    entries.iter().enumerate().map(|(index, entry)| format!("#{} {}", index, entry)).collect()
+}
@@ -1,6 +1,18 @@
 use crate::test_utils::*;
+use crate::events::decode_event_page;
+use crate::storage::{PointerStorage, Storage, WriteBatch};
+use crate::response::{encode_legacy, encode_response, STATUS_OK};
+#[cfg(not(feature = "legacy-responses"))]
+use crate::response::decode_response;
+#[cfg(feature = "legacy-responses")]
+use crate::AlreadyInitialized;
+use crate::exchange::{compute_exchange, ExchangeBalances};
+use crate::dispatch::{alkane_address, dispatch, Call, IntoOutput, OpcodeInputs, Output};
+use crate::inputs::{decode_amount_nonzero, decode_index, decode_limit, decode_opcode, LimitOutOfRange};
+use crate::{ClaimWindow, ErrorCode, ErrorFrame, RewardSchedule, SupplyView, SwapOffer, Event, EventKind, InitParams, InvariantReport, Opcode, Token, MAX_PAGE_SIZE, SCHEMA_VERSION};
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use super::*;
 
@@ -12,7 +24,7 @@ mod tests {
     // Helper function to extract u128 from response data
     fn extract_u128(response: &CallResponse) -> u128 {
         let mut bytes = [0u8; 16];
-        bytes.copy_from_slice(&response.data[0..16]);
+        bytes.copy_from_slice(&payload(response)[0..16]);
         u128::from_le_bytes(bytes)
     }
 
@@ -74,9 +86,21 @@ mod tests {
         assert_eq!(harness.contract.total_ooga(), 1);
         assert_eq!(harness.contract.total_booga(), 0);
         
-        // Exchange OOGA for BOOGA
-        let result = harness.execute(2, vec![address.clone()]);
+        // Exchange OOGA for BOOGA a few blocks later, touching only the two balances,
+        // the two totals and the address's last-active height
+        harness.set_height(7);
+        let (result, diff) = harness.execute_with_diff(2, vec![address.clone()]);
         assert!(result.is_ok());
+        let ooga_key = harness.contract.ooga_balance_key(&address);
+        let booga_key = harness.contract.booga_balance_key(&address);
+        let last_active_key = format!("/last-active/{}", address);
+        diff.assert_only_touched(&[&ooga_key, &booga_key, "/total-ooga", "/total-booga", &last_active_key]);
+        assert!(diff.added.contains_key(&booga_key));
+        assert!(diff.removed.contains_key(&ooga_key));
+        assert_eq!(diff.new_u128(&booga_key), Some(1));
+        assert_eq!(diff.new_u128("/total-ooga"), Some(0));
+        assert_eq!(diff.new_u128("/total-booga"), Some(1));
+        assert_eq!(diff.changed[&last_active_key], (0u128.to_le_bytes().to_vec(), 7u128.to_le_bytes().to_vec()));
         
         // Final balances
         assert_eq!(harness.contract.ooga_balance_of(&address), 0);
@@ -94,8 +118,9 @@ mod tests {
         let _ = harness.execute(0, vec![]);
         
         // Try to exchange without claiming first
-        let result = harness.execute(2, vec![address.clone()]);
+        let (result, diff) = harness.execute_with_diff(2, vec![address.clone()]);
         assert!(result.is_err());
+        assert!(diff.is_empty());
         
         // Error should be about insufficient balance
         if let Err(e) = result {
@@ -296,4 +321,2851 @@ mod tests {
         assert_eq!(harness.contract.total_ooga(), 50);
         assert_eq!(harness.contract.total_booga(), 50);
     }
+
+    #[test]
+    fn test_claim_cap_per_block() {
+        let harness = TestHarness::new();
+        harness.set_height(100);
+
+        // Initialize contract with a cap of 3 claims per block
+        let result = harness.execute(0, vec!["3".to_string()]);
+        assert!(result.is_ok());
+        assert_eq!(harness.contract.claim_cap(), 3);
+
+        // Fill the block to the cap with fresh addresses
+        for i in 0..3 {
+            let result = harness.execute(1, vec![format!("user{}", i)]);
+            assert!(result.is_ok());
+        }
+        assert_eq!(harness.contract.claims_at_height(100), 3);
+
+        // The next claim in the same block should fail without minting
+        let result = harness.execute(1, vec!["user3".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("claim limit reached"));
+        }
+        assert_eq!(harness.contract.ooga_balance_of("user3"), 0);
+        assert_eq!(harness.contract.total_ooga(), 3);
+
+        // Advancing the height resets the counter
+        harness.set_height(101);
+        let result = harness.execute(1, vec!["user3".to_string()]);
+        assert!(result.is_ok());
+        assert_eq!(harness.contract.ooga_balance_of("user3"), 1);
+        assert_eq!(harness.contract.claims_at_height(101), 1);
+        assert_eq!(harness.contract.total_ooga(), 4);
+    }
+
+    #[test]
+    fn test_claim_cap_query() {
+        let harness = TestHarness::new();
+        harness.set_height(7);
+
+        // Initialize contract with a cap of 5 claims per block
+        let _ = harness.execute(0, vec!["5".to_string()]);
+        let _ = harness.execute(1, vec![test_address()]);
+        let _ = harness.execute(1, vec![test_address()]);
+
+        // Query cap and claims in the current block
+        let result = harness.execute(7, vec![]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128_at(&response, 0), 5);
+            assert_eq!(extract_u128_at(&response, 1), 2);
+        }
+
+        // A new block reports no claims yet
+        harness.set_height(8);
+        let result = harness.execute(7, vec![]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128_at(&response, 0), 5);
+            assert_eq!(extract_u128_at(&response, 1), 0);
+        }
+    }
+
+    #[test]
+    fn test_uncapped_claims_by_default() {
+        let harness = TestHarness::new();
+
+        // Initialize contract without a cap
+        let _ = harness.execute(0, vec![]);
+        assert_eq!(harness.contract.claim_cap(), 0);
+
+        // Many claims in the same block are all accepted
+        for i in 0..20 {
+            let result = harness.execute(1, vec![format!("user{}", i)]);
+            assert!(result.is_ok());
+        }
+        assert_eq!(harness.contract.total_ooga(), 20);
+    }
+
+    #[test]
+    fn test_vesting_unlocks_linearly() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        // Initialize contract and grant 1000 OOGA vesting from height 100 over 200 blocks
+        let _ = harness.execute(0, vec![]);
+        let result = harness.execute(8, vec![
+            address.clone(), "1000".to_string(), "100".to_string(), "200".to_string(),
+        ]);
+        assert!(result.is_ok());
+
+        // 0%: nothing can be claimed at the start height
+        harness.set_height(100);
+        let result = harness.execute(9, vec![address.clone()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("no vested OOGA available"));
+        }
+        assert_eq!(harness.contract.ooga_balance_of(&address), 0);
+
+        // 50%: half of the grant unlocks halfway through
+        harness.set_height(200);
+        let result = harness.execute(10, vec![address.clone()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128_at(&response, 0), 1000);
+            assert_eq!(extract_u128_at(&response, 1), 0);
+            assert_eq!(extract_u128_at(&response, 2), 500);
+        }
+        let result = harness.execute(9, vec![address.clone()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128(&response), 500);
+        }
+        assert_eq!(harness.contract.ooga_balance_of(&address), 500);
+        assert_eq!(harness.contract.total_ooga(), 500);
+
+        // Claiming again in the same block yields nothing new
+        let result = harness.execute(9, vec![address.clone()]);
+        assert!(result.is_err());
+
+        // 100%: the remainder unlocks at start + duration
+        harness.set_height(300);
+        let result = harness.execute(9, vec![address.clone()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128(&response), 500);
+        }
+        assert_eq!(harness.contract.ooga_balance_of(&address), 1000);
+        assert_eq!(harness.contract.total_ooga(), 1000);
+
+        // Past the end: fully claimed, nothing more to unlock
+        harness.set_height(10_000);
+        let result = harness.execute(10, vec![address.clone()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128_at(&response, 0), 1000);
+            assert_eq!(extract_u128_at(&response, 1), 1000);
+            assert_eq!(extract_u128_at(&response, 2), 0);
+        }
+        let result = harness.execute(9, vec![address.clone()]);
+        assert!(result.is_err());
+        assert_eq!(harness.contract.ooga_balance_of(&address), 1000);
+    }
+
+    #[test]
+    fn test_vesting_claim_after_end_in_one_step() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        // Initialize contract and grant 999 OOGA vesting from height 10 over 7 blocks
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(8, vec![
+            address.clone(), "999".to_string(), "10".to_string(), "7".to_string(),
+        ]);
+
+        // Before the start nothing is claimable
+        harness.set_height(5);
+        assert!(harness.execute(9, vec![address.clone()]).is_err());
+
+        // Well past the end the whole grant is claimed at once
+        harness.set_height(500);
+        let result = harness.execute(9, vec![address.clone()]);
+        assert!(result.is_ok());
+        assert_eq!(harness.contract.ooga_balance_of(&address), 999);
+    }
+
+    #[test]
+    fn test_vesting_grant_requires_owner() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        // Initialize contract as the default caller
+        let _ = harness.execute(0, vec![]);
+        assert_eq!(harness.contract.owner(), DEFAULT_CALLER);
+
+        // A different caller cannot create grants
+        harness.set_caller("mallory");
+        let result = harness.execute(8, vec![
+            address.clone(), "1000".to_string(), "0".to_string(), "10".to_string(),
+        ]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("caller is not the owner"));
+        }
+        assert!(harness.contract.vesting_grant(&address).is_none());
+    }
+
+    #[test]
+    fn test_single_vesting_grant_per_address() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        // Initialize contract and create a grant
+        let _ = harness.execute(0, vec![]);
+        let result = harness.execute(8, vec![
+            address.clone(), "1000".to_string(), "0".to_string(), "10".to_string(),
+        ]);
+        assert!(result.is_ok());
+
+        // A second grant for the same address is rejected
+        let result = harness.execute(8, vec![
+            address.clone(), "5".to_string(), "0".to_string(), "10".to_string(),
+        ]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("vesting grant already exists"));
+        }
+        assert_eq!(harness.contract.vesting_grant(&address).map(|g| g.total), Some(1000));
+    }
+
+    #[test]
+    fn test_initialization_echoes_parameters() {
+        let harness = TestHarness::new();
+        harness.set_caller("deploy_key");
+
+        // Initialize contract with every parameter set
+        let inputs = ["42", "5", "1000", "10", "90", "8", "50"];
+        let result = harness.execute(0, inputs.iter().map(|input| input.to_string()).collect());
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            let params = InitParams::decode(payload(&response)).unwrap();
+            assert_eq!(params.schema_version, SCHEMA_VERSION);
+            assert_eq!(params.claim_cap, 42);
+            assert_eq!(params.referral_bonus, 5);
+            assert_eq!(params.max_supply, 1000);
+            assert_eq!(params.claim_window, ClaimWindow { start: 10, end: 90 });
+            assert_eq!(params.base_reward, 8);
+            assert_eq!(params.halving_interval, 50);
+            assert_eq!(params.owner, "deploy_key");
+            assert_eq!(params, harness.contract.init_params());
+        }
+    }
+
+    #[test]
+    fn test_init_params_round_trip() {
+        let params = InitParams {
+            schema_version: SCHEMA_VERSION,
+            claim_cap: 3,
+            referral_bonus: u128::MAX,
+            max_supply: 21_000_000,
+            claim_window: ClaimWindow { start: 7, end: u64::MAX },
+            base_reward: 64,
+            halving_interval: 210_000,
+            owner: "2:1".to_string(),
+        };
+        let data = params.encode();
+        assert_eq!(data.len(), InitParams::HEADER_LEN + 3);
+        assert_eq!(InitParams::decode(&data).unwrap(), params);
+    }
+
+    #[test]
+    fn test_reinitialization_rejected() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        // Initialize contract and mint some OOGA
+        let _ = harness.execute(0, vec!["10".to_string()]);
+        let _ = harness.execute(1, vec![address.clone()]);
+
+        // A second initialization from another caller is rejected
+        harness.set_caller("mallory");
+        let result = harness.execute(0, vec!["0".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(error_code(&e), Some(ErrorCode::AlreadyInitialized));
+            assert!(e.to_string().starts_with("contract already initialized"));
+            // The error echoes the parameters recorded by the first initialization,
+            // in the frame's detail when responses are framed
+            #[cfg(not(feature = "legacy-responses"))]
+            let params = InitParams::decode(&e.downcast_ref::<ErrorFrame>().unwrap().detail).unwrap();
+            #[cfg(feature = "legacy-responses")]
+            let params = e.downcast_ref::<AlreadyInitialized>().unwrap().0.clone();
+            assert_eq!(params, harness.contract.init_params());
+            assert_eq!(params.schema_version, SCHEMA_VERSION);
+            assert_eq!(params.claim_cap, 10);
+            assert_eq!(params.owner, DEFAULT_CALLER);
+        }
+
+        // Existing state is left untouched
+        assert_eq!(harness.contract.owner(), DEFAULT_CALLER);
+        assert_eq!(harness.contract.claim_cap(), 10);
+        assert_eq!(harness.contract.total_ooga(), 1);
+    }
+
+    #[test]
+    fn test_init_params_decode_rejects_short_payload() {
+        assert!(InitParams::decode(&[0u8; InitParams::HEADER_LEN - 1]).is_err());
+        let params = InitParams::decode(&[0u8; InitParams::HEADER_LEN]).unwrap();
+        assert_eq!(params.owner, "");
+    }
+
+    #[test]
+    fn test_ooga_allowance_transfer_from() {
+        let harness = TestHarness::new();
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+        let carol = "carol".to_string();
+
+        // Initialize contract and give Alice 3 OOGA
+        let _ = harness.execute(0, vec![]);
+        for _ in 0..3 {
+            let _ = harness.execute(1, vec![alice.clone()]);
+        }
+
+        // Alice approves Bob for 2 OOGA
+        harness.set_caller(&alice);
+        let result = harness.execute(11, vec![bob.clone(), "2".to_string()]);
+        assert!(result.is_ok());
+        let result = harness.execute(12, vec![alice.clone(), bob.clone()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128(&response), 2);
+        }
+
+        // Bob moves 2 OOGA from Alice to Carol
+        harness.set_caller(&bob);
+        let result = harness.execute(13, vec![alice.clone(), carol.clone(), "2".to_string()]);
+        assert!(result.is_ok());
+        assert_eq!(harness.contract.ooga_balance_of(&alice), 1);
+        assert_eq!(harness.contract.ooga_balance_of(&carol), 2);
+        assert_eq!(harness.contract.allowance(Token::Ooga, &alice, &bob), 0);
+        assert_eq!(harness.contract.total_ooga(), 3);
+
+        // The allowance is exhausted
+        let result = harness.execute(13, vec![alice.clone(), carol.clone(), "1".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("insufficient OOGA allowance"));
+        }
+    }
+
+    #[test]
+    fn test_booga_allowance_transfer_from() {
+        let harness = TestHarness::new();
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+
+        // Initialize contract and give Alice 2 BOOGA
+        let _ = harness.execute(0, vec![]);
+        for _ in 0..2 {
+            let _ = harness.execute(1, vec![alice.clone()]);
+            let _ = harness.execute(2, vec![alice.clone()]);
+        }
+
+        // Alice approves Bob for 5 BOOGA
+        harness.set_caller(&alice);
+        let result = harness.execute(14, vec![bob.clone(), "5".to_string()]);
+        assert!(result.is_ok());
+        let result = harness.execute(15, vec![alice.clone(), bob.clone()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128(&response), 5);
+        }
+
+        // The OOGA allowance is independent of the BOOGA one
+        assert_eq!(harness.contract.allowance(Token::Ooga, &alice, &bob), 0);
+
+        // Bob cannot move more BOOGA than Alice holds
+        harness.set_caller(&bob);
+        let result = harness.execute(16, vec![alice.clone(), bob.clone(), "3".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("insufficient BOOGA balance"));
+        }
+
+        // Bob moves 2 BOOGA to himself
+        let result = harness.execute(16, vec![alice.clone(), bob.clone(), "2".to_string()]);
+        assert!(result.is_ok());
+        assert_eq!(harness.contract.booga_balance_of(&alice), 0);
+        assert_eq!(harness.contract.booga_balance_of(&bob), 2);
+        assert_eq!(harness.contract.allowance(Token::Booga, &alice, &bob), 3);
+        assert_eq!(harness.contract.total_booga(), 2);
+    }
+
+    #[test]
+    fn test_spendable_allowance_exceeds_balance() {
+        let harness = TestHarness::new();
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+
+        // Alice holds 2 OOGA and 1 BOOGA but approves Bob for 10 of each
+        let _ = harness.execute(0, vec![]);
+        for _ in 0..3 {
+            let _ = harness.execute(1, vec![alice.clone()]);
+        }
+        let _ = harness.execute(2, vec![alice.clone()]);
+        harness.set_caller(&alice);
+        let _ = harness.execute(11, vec![bob.clone(), "10".to_string()]);
+        let _ = harness.execute(14, vec![bob.clone(), "10".to_string()]);
+
+        // Spendable amounts are capped by the balances
+        let result = harness.execute(17, vec![alice.clone(), bob.clone()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128(&response), 2);
+        }
+        let result = harness.execute(18, vec![alice.clone(), bob.clone()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128(&response), 1);
+        }
+    }
+
+    #[test]
+    fn test_spendable_balance_exceeds_allowance() {
+        let harness = TestHarness::new();
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+
+        // Alice holds 5 OOGA and 3 BOOGA but approves Bob for 1 of each
+        let _ = harness.execute(0, vec![]);
+        for _ in 0..8 {
+            let _ = harness.execute(1, vec![alice.clone()]);
+        }
+        for _ in 0..3 {
+            let _ = harness.execute(2, vec![alice.clone()]);
+        }
+        harness.set_caller(&alice);
+        let _ = harness.execute(11, vec![bob.clone(), "1".to_string()]);
+        let _ = harness.execute(14, vec![bob.clone(), "1".to_string()]);
+
+        // Spendable amounts are capped by the allowances
+        let result = harness.execute(17, vec![alice.clone(), bob.clone()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128(&response), 1);
+        }
+        let result = harness.execute(18, vec![alice.clone(), bob.clone()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128(&response), 1);
+        }
+
+        // Without an approval nothing is spendable
+        let result = harness.execute(17, vec![alice.clone(), "carol".to_string()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128(&response), 0);
+        }
+    }
+
+    #[test]
+    fn test_transfer_from_to_owner_keeps_balance() {
+        let harness = TestHarness::new();
+        let alice = "alice".to_string();
+
+        // Alice approves herself and transfers to herself
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(1, vec![alice.clone()]);
+        harness.set_caller(&alice);
+        let _ = harness.execute(11, vec![alice.clone(), "1".to_string()]);
+        let result = harness.execute(13, vec![alice.clone(), alice.clone(), "1".to_string()]);
+        assert!(result.is_ok());
+        assert_eq!(harness.contract.ooga_balance_of(&alice), 1);
+        assert_eq!(harness.contract.allowance(Token::Ooga, &alice, &alice), 0);
+    }
+
+    #[test]
+    fn test_adjust_balance_up() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        // Initialize contract and claim 2 OOGA
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(1, vec![address.clone()]);
+        let _ = harness.execute(1, vec![address.clone()]);
+
+        // Owner raises the OOGA balance to 10 with reason code 7
+        harness.set_height(55);
+        let result = harness.execute(19, vec!["0".to_string(), address.clone(), "10".to_string(), "7".to_string()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128(&response), 0);
+        }
+        assert_eq!(harness.contract.ooga_balance_of(&address), 10);
+        assert_eq!(harness.contract.total_ooga(), 10);
+
+        // The audit record is in the event log
+        let event = harness.contract.event(0).unwrap();
+        assert_eq!(event, Event {
+            kind: EventKind::BalanceAdjusted,
+            token: Token::Ooga,
+            address: address.clone(),
+            old_value: 2,
+            new_value: 10,
+            height: 55,
+            reason: 7,
+        });
+    }
+
+    #[test]
+    fn test_adjust_balance_down() {
+        let harness = TestHarness::new();
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+
+        // Initialize contract and give both users 3 BOOGA
+        let _ = harness.execute(0, vec![]);
+        for address in [&alice, &bob] {
+            for _ in 0..3 {
+                let _ = harness.execute(1, vec![address.clone()]);
+                let _ = harness.execute(2, vec![address.clone()]);
+            }
+        }
+        assert_eq!(harness.contract.total_booga(), 6);
+
+        // Owner lowers Bob's BOOGA balance to 1
+        let result = harness.execute(19, vec!["1".to_string(), bob.clone(), "1".to_string(), "2".to_string()]);
+        assert!(result.is_ok());
+        assert_eq!(harness.contract.booga_balance_of(&bob), 1);
+        assert_eq!(harness.contract.booga_balance_of(&alice), 3);
+        assert_eq!(harness.contract.total_booga(), 4);
+        assert_eq!(harness.contract.total_ooga(), 0);
+
+        let event = harness.contract.event(0).unwrap();
+        assert_eq!(event.token, Token::Booga);
+        assert_eq!(event.old_value, 3);
+        assert_eq!(event.new_value, 1);
+    }
+
+    #[test]
+    fn test_adjust_balance_underflow_is_atomic() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        // Initialize contract, claim 5 OOGA and then corrupt the total
+        let _ = harness.execute(0, vec![]);
+        for _ in 0..5 {
+            let _ = harness.execute(1, vec![address.clone()]);
+        }
+        harness.contract.set_total_ooga(2);
+
+        // Dropping the balance to 0 would take the total below zero
+        let result = harness.execute(19, vec!["0".to_string(), address.clone(), "0".to_string(), "1".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("total OOGA supply underflow"));
+        }
+
+        // Nothing was written
+        assert_eq!(harness.contract.ooga_balance_of(&address), 5);
+        assert_eq!(harness.contract.total_ooga(), 2);
+        assert_eq!(harness.contract.event_count(), 0);
+    }
+
+    #[test]
+    fn test_noop_adjustment_is_recorded() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        // Initialize contract and claim 1 OOGA
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(1, vec![address.clone()]);
+
+        // Setting the balance to its current value still leaves an audit record
+        let result = harness.execute(19, vec!["0".to_string(), address.clone(), "1".to_string(), "3".to_string()]);
+        assert!(result.is_ok());
+        assert_eq!(harness.contract.ooga_balance_of(&address), 1);
+        assert_eq!(harness.contract.total_ooga(), 1);
+        assert_eq!(harness.contract.event_count(), 1);
+        let event = harness.contract.event(0).unwrap();
+        assert_eq!(event.old_value, 1);
+        assert_eq!(event.new_value, 1);
+        assert_eq!(event.reason, 3);
+    }
+
+    #[test]
+    fn test_adjust_balance_requires_owner() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        // Initialize contract and try to adjust from another caller
+        let _ = harness.execute(0, vec![]);
+        harness.set_caller("mallory");
+        let result = harness.execute(19, vec!["0".to_string(), address.clone(), "1000".to_string(), "0".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("caller is not the owner"));
+        }
+        assert_eq!(harness.contract.ooga_balance_of(&address), 0);
+        assert_eq!(harness.contract.event_count(), 0);
+
+        // Unknown token codes are rejected
+        harness.set_caller(DEFAULT_CALLER);
+        let result = harness.execute(19, vec!["2".to_string(), address.clone(), "1".to_string(), "0".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("unknown token"));
+        }
+    }
+
+    #[test]
+    fn test_event_log_query() {
+        let harness = TestHarness::new();
+
+        // Initialize contract and record three adjustments
+        let _ = harness.execute(0, vec![]);
+        for (i, address) in ["alice", "bob", "carol"].iter().enumerate() {
+            let result = harness.execute(19, vec!["0".to_string(), address.to_string(), (i + 1).to_string(), "0".to_string()]);
+            assert!(result.is_ok());
+        }
+
+        // Fetch everything from sequence 1
+        let result = harness.execute(20, vec!["1".to_string(), "10".to_string()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            let entries = decode_event_page(payload(&response)).unwrap();
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].0, 1);
+            assert_eq!(entries[0].1.address, "bob");
+            assert_eq!(entries[1].0, 2);
+            assert_eq!(entries[1].1.address, "carol");
+            assert_eq!(entries[1].1.new_value, 3);
+        }
+
+        // The limit bounds the page and an offset past the end is empty
+        let result = harness.execute(20, vec!["0".to_string(), "1".to_string()]);
+        if let Ok(response) = result {
+            assert_eq!(decode_event_page(payload(&response)).unwrap().len(), 1);
+        }
+        let result = harness.execute(20, vec!["3".to_string(), "10".to_string()]);
+        if let Ok(response) = result {
+            assert!(payload(&response).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_holder_index_registration() {
+        let harness = TestHarness::new();
+
+        // Initialize contract and have two users claim, one of them twice
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+        let _ = harness.execute(1, vec!["bob".to_string()]);
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+
+        // Each address is indexed once, in order of first credit
+        assert_eq!(harness.contract.holder_count(), 2);
+        assert_eq!(harness.contract.holders(0, 10), vec!["alice".to_string(), "bob".to_string()]);
+        assert!(harness.contract.is_holder("alice"));
+        assert!(!harness.contract.is_holder("carol"));
+    }
+
+    #[test]
+    fn test_invariants_pass() {
+        let harness = TestHarness::new();
+
+        // Initialize contract and build up some balances
+        let _ = harness.execute(0, vec![]);
+        for address in ["alice", "bob", "carol"] {
+            for _ in 0..3 {
+                let _ = harness.execute(1, vec![address.to_string()]);
+            }
+            let _ = harness.execute(2, vec![address.to_string()]);
+        }
+
+        let report = harness.contract.check_invariants();
+        assert!(report.passed());
+        assert_eq!(report.ooga_sum, 6);
+        assert_eq!(report.booga_sum, 3);
+        assert_eq!(report.holder_count, 3);
+
+        // The opcode returns the same report
+        let result = harness.execute(21, vec![]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(payload(&response)[96], 1);
+            assert_eq!(InvariantReport::decode(payload(&response)).unwrap(), report);
+        }
+    }
+
+    #[test]
+    fn test_invariants_detect_corrupted_total() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        // Initialize contract, claim and then corrupt the OOGA total
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(1, vec![address.clone()]);
+        let _ = harness.execute(1, vec![address.clone()]);
+        harness.contract.set_total_ooga(5);
+
+        let report = harness.contract.check_invariants();
+        assert!(!report.passed());
+        assert_eq!(report.ooga_sum, 2);
+        assert_eq!(report.total_ooga, 5);
+        assert_eq!(report.booga_sum, report.total_booga);
+
+        let result = harness.execute(21, vec![]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(payload(&response)[96], 0);
+        }
+    }
+
+    #[test]
+    fn test_invariants_paged_with_carried_sums() {
+        let harness = TestHarness::new();
+
+        // Initialize contract and give five holders i + 1 OOGA each
+        let _ = harness.execute(0, vec![]);
+        for i in 0..5 {
+            for _ in 0..=i {
+                let _ = harness.execute(1, vec![format!("user{}", i)]);
+            }
+        }
+
+        // Walk the holders two at a time, carrying the sums in the inputs
+        let mut start = 0;
+        let mut ooga_carry = 0;
+        let mut booga_carry = 0;
+        let mut pages = 0;
+        let report = loop {
+            let result = harness.execute(21, vec![
+                start.to_string(), "2".to_string(), ooga_carry.to_string(), booga_carry.to_string(),
+            ]);
+            let report = InvariantReport::decode(payload(&result.unwrap())).unwrap();
+            pages += 1;
+            if report.is_complete() {
+                break report;
+            }
+            assert!(!report.passed());
+            start = report.next_index;
+            ooga_carry = report.ooga_sum;
+            booga_carry = report.booga_sum;
+        };
+
+        assert_eq!(pages, 3);
+        assert!(report.passed());
+        assert_eq!(report.ooga_sum, 15);
+        assert_eq!(report, harness.contract.check_invariants());
+    }
+
+    #[test]
+    fn test_invariants_default_to_one_page() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        for i in 0..=MAX_PAGE_SIZE {
+            let _ = harness.execute(1, vec![format!("user{}", i)]);
+        }
+
+        // Without a limit a single page is checked and the rest is left for the next call
+        let report = InvariantReport::decode(payload(&harness.execute(21, vec![]).unwrap())).unwrap();
+        assert_eq!(report.next_index, MAX_PAGE_SIZE);
+        assert!(!report.is_complete());
+        let inputs = vec![MAX_PAGE_SIZE.to_string(), "1".to_string(), report.ooga_sum.to_string(), report.booga_sum.to_string()];
+        let report = InvariantReport::decode(payload(&harness.execute(21, inputs).unwrap())).unwrap();
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn test_failed_commit_leaves_no_partial_exchange() {
+        let storage = FailingStorage::new(usize::MAX);
+        let harness = TestHarness::with_storage(Box::new(storage.clone()));
+        let address = test_address();
+
+        // Initialize contract and claim OOGA with an unlimited write budget
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(1, vec![address.clone()]);
+
+        // The exchange needs four writes but the backend fails on the third
+        let before = snapshot_storage();
+        storage.set_remaining_writes(2);
+        let result = harness.execute(2, vec![address.clone()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("storage write failed"));
+        }
+
+        // The two writes that went through were rolled back, so no key of the batch landed
+        assert_eq!(storage.remaining_writes(), 0);
+        assert_eq!(snapshot_storage(), before);
+        assert_eq!(harness.contract.ooga_balance_of(&address), 1);
+        assert_eq!(harness.contract.booga_balance_of(&address), 0);
+        assert_eq!(harness.contract.total_ooga(), 1);
+        assert_eq!(harness.contract.total_booga(), 0);
+        assert!(harness.contract.check_invariants().passed());
+
+        // Once the backend recovers the exchange goes through
+        storage.set_remaining_writes(usize::MAX);
+        let result = harness.execute(2, vec![address.clone()]);
+        assert!(result.is_ok());
+        assert_eq!(harness.contract.ooga_balance_of(&address), 0);
+        assert_eq!(harness.contract.booga_balance_of(&address), 1);
+    }
+
+    #[test]
+    fn test_failing_storage_counts_direct_writes() {
+        let storage = FailingStorage::new(2);
+        assert!(storage.set_u128("/a", 1).is_ok());
+        assert!(storage.set_bytes("/b", vec![1]).is_ok());
+        assert!(storage.delete("/a").is_err());
+        assert_eq!(storage.get_u128("/a"), 1);
+
+        // A commit fails on the first write past the budget and undoes the ones before it
+        storage.set_remaining_writes(2);
+        let mut batch = WriteBatch::default();
+        batch.writes.insert("/a".to_string(), None);
+        batch.writes.insert("/b".to_string(), Some(vec![2; 16]));
+        batch.writes.insert("/c".to_string(), Some(vec![3; 16]));
+        assert!(storage.commit(batch).is_err());
+        assert_eq!(storage.remaining_writes(), 0);
+        assert_eq!(storage.get_u128("/a"), 1);
+        assert_eq!(storage.get_bytes("/b"), vec![1]);
+        assert!(storage.get_bytes("/c").is_empty());
+    }
+
+    #[test]
+    fn test_batched_writes_commit_only_on_success() {
+        let harness = TestHarness::new();
+        let key = harness.contract.ooga_balance_key("alice");
+
+        // Staged writes are visible to reads in the same operation but reach storage only on commit
+        let result = harness.contract.atomically(|| {
+            harness.contract.set_ooga_balance("alice", 5);
+            assert_eq!(harness.contract.ooga_balance_of("alice"), 5);
+            assert_eq!(PointerStorage.get_u128(&key), 0);
+            Ok(())
+        });
+        assert!(result.is_ok());
+        assert_eq!(PointerStorage.get_u128(&key), 5);
+
+        // A failed operation discards everything it staged
+        let result: anyhow::Result<()> = harness.contract.atomically(|| {
+            harness.contract.set_ooga_balance("alice", 9);
+            harness.contract.set_total_ooga(9);
+            Err(anyhow::anyhow!("operation failed"))
+        });
+        assert!(result.is_err());
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 5);
+        assert_eq!(harness.contract.total_ooga(), 0);
+    }
+
+    #[test]
+    fn test_address_events_paginate() {
+        let harness = TestHarness::new();
+
+        // Initialize contract and record ten adjustments for alice interleaved with bob's
+        let _ = harness.execute(0, vec![]);
+        for i in 0..10 {
+            let _ = harness.execute(19, vec!["0".to_string(), "alice".to_string(), (i + 1).to_string(), "0".to_string()]);
+            let _ = harness.execute(19, vec!["1".to_string(), "bob".to_string(), (i + 1).to_string(), "0".to_string()]);
+        }
+        assert_eq!(harness.contract.address_event_count("alice"), 10);
+
+        // Pages of four cross the page boundaries without gaps or repeats
+        let mut seen = Vec::new();
+        for start in [0, 4, 8] {
+            let result = harness.execute(22, vec!["alice".to_string(), start.to_string(), "4".to_string()]);
+            assert!(result.is_ok());
+            let entries = decode_event_page(payload(&result.unwrap())).unwrap();
+            assert_eq!(entries.len(), if start == 8 { 2 } else { 4 });
+            for (sequence, event) in entries {
+                assert_eq!(event.address, "alice");
+                assert_eq!(event.token, Token::Ooga);
+                seen.push((sequence, event.new_value));
+            }
+        }
+        let expected: Vec<(u128, u128)> = (0..10).map(|i| (i * 2, i + 1)).collect();
+        assert_eq!(seen, expected);
+
+        // The records use the same layout as the global query
+        let global = harness.execute(20, vec!["2".to_string(), "1".to_string()]).unwrap();
+        let by_address = harness.execute(22, vec!["alice".to_string(), "1".to_string(), "1".to_string()]).unwrap();
+        assert_eq!(payload(&global), payload(&by_address));
+    }
+
+    #[test]
+    fn test_address_without_events_returns_empty_page() {
+        let harness = TestHarness::new();
+
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(19, vec!["0".to_string(), "alice".to_string(), "1".to_string(), "0".to_string()]);
+
+        let result = harness.execute(22, vec!["nobody".to_string(), "0".to_string(), "10".to_string()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert!(payload(&response).is_empty());
+        }
+
+        // An offset past the end is empty as well
+        let result = harness.execute(22, vec!["alice".to_string(), "5".to_string(), "10".to_string()]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert!(payload(&response).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_address_index_committed_with_event() {
+        let storage = FailingStorage::new(usize::MAX);
+        let harness = TestHarness::with_storage(Box::new(storage.clone()));
+        let _ = harness.execute(0, vec![]);
+
+        // A failed commit drops the event and its index entry together
+        storage.set_remaining_writes(3);
+        let result = harness.execute(19, vec!["0".to_string(), "alice".to_string(), "1".to_string(), "0".to_string()]);
+        assert!(result.is_err());
+        assert_eq!(harness.contract.event_count(), 0);
+        assert_eq!(harness.contract.address_event_count("alice"), 0);
+    }
+
+    #[test]
+    fn test_referral_bonus_credited() {
+        let harness = TestHarness::new();
+
+        // Initialize contract with no claim cap and a referral bonus of 5
+        let _ = harness.execute(0, vec!["0".to_string(), "5".to_string()]);
+
+        // Referred claims credit the referrer on top of the claimer
+        let result = harness.execute(1, vec!["alice".to_string(), "bob".to_string()]);
+        assert!(result.is_ok());
+        let _ = harness.execute(1, vec!["alice".to_string(), "bob".to_string()]);
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 2);
+        assert_eq!(harness.contract.ooga_balance_of("bob"), 10);
+        assert_eq!(harness.contract.total_ooga(), 12);
+
+        // Claims without a referrer pay no bonus
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+        assert_eq!(harness.contract.ooga_balance_of("bob"), 10);
+
+        // Query the recorded referrer and the referrer's earnings
+        let response = harness.execute(23, vec!["alice".to_string()]).unwrap();
+        assert_eq!(payload(&response), b"bob".to_vec());
+        let response = harness.execute(23, vec!["carol".to_string()]).unwrap();
+        assert!(payload(&response).is_empty());
+        let response = harness.execute(24, vec!["bob".to_string()]).unwrap();
+        assert_eq!(extract_u128(&response), 10);
+    }
+
+    #[test]
+    fn test_referrer_pinned_on_first_use() {
+        let harness = TestHarness::new();
+
+        let _ = harness.execute(0, vec!["0".to_string(), "5".to_string()]);
+        let _ = harness.execute(1, vec!["alice".to_string(), "bob".to_string()]);
+
+        // A different referrer is rejected and the claim is not applied
+        let result = harness.execute(1, vec!["alice".to_string(), "carol".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("referrer already set to bob"));
+        }
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 1);
+        assert_eq!(harness.contract.ooga_balance_of("carol"), 0);
+        assert_eq!(harness.contract.referrer_of("alice"), Some("bob".to_string()));
+    }
+
+    #[test]
+    fn test_self_referral_rejected() {
+        let harness = TestHarness::new();
+
+        let _ = harness.execute(0, vec!["0".to_string(), "5".to_string()]);
+
+        let result = harness.execute(1, vec!["alice".to_string(), "alice".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("cannot refer yourself"));
+        }
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 0);
+        assert_eq!(harness.contract.total_ooga(), 0);
+        assert_eq!(harness.contract.referrer_of("alice"), None);
+    }
+
+    #[test]
+    fn test_referrals_disabled_by_default() {
+        let harness = TestHarness::new();
+
+        let _ = harness.execute(0, vec![]);
+        let result = harness.execute(1, vec!["alice".to_string(), "bob".to_string()]);
+        assert!(result.is_ok());
+        assert_eq!(harness.contract.ooga_balance_of("bob"), 0);
+        assert_eq!(harness.contract.referrer_of("alice"), None);
+    }
+
+    #[test]
+    fn test_referral_bonus_respects_supply_cap() {
+        let harness = TestHarness::new();
+
+        // Supply cap of 10 with a referral bonus of 5
+        let _ = harness.execute(0, vec!["0".to_string(), "5".to_string(), "10".to_string()]);
+        let _ = harness.execute(1, vec!["alice".to_string(), "bob".to_string()]);
+        assert_eq!(harness.contract.total_ooga(), 6);
+
+        // The claim itself fits but the bonus would exceed the cap, so neither is applied
+        let result = harness.execute(1, vec!["alice".to_string(), "bob".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("OOGA supply cap of 10 reached"));
+        }
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 1);
+        assert_eq!(harness.contract.referral_earnings("bob"), 5);
+        assert_eq!(harness.contract.total_ooga(), 6);
+
+        // Unreferred claims can still fill the remaining supply
+        for _ in 0..4 {
+            assert!(harness.execute(1, vec!["alice".to_string()]).is_ok());
+        }
+        assert_eq!(harness.contract.total_ooga(), 10);
+        assert!(harness.execute(1, vec!["alice".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_adjustments_respect_supply_cap() {
+        let harness = TestHarness::new();
+        let adjust = |token: &str, address: &str, value: &str| {
+            harness.execute(19, vec![token.to_string(), address.to_string(), value.to_string(), "0".to_string()])
+        };
+
+        // Supply cap of 10
+        let _ = harness.execute(0, vec!["0".to_string(), "0".to_string(), "10".to_string()]);
+        assert!(adjust("0", "alice", "6").is_ok());
+
+        // Raising OOGA past the cap is rejected and leaves the balance alone
+        let result = adjust("0", "bob", "5");
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("OOGA supply cap of 10 reached"));
+        }
+        assert_eq!(harness.contract.ooga_balance_of("bob"), 0);
+        assert_eq!(harness.contract.total_ooga(), 6);
+
+        // Raising up to the cap, lowering, and BOOGA are unaffected
+        assert!(adjust("0", "bob", "4").is_ok());
+        assert!(adjust("0", "alice", "1").is_ok());
+        assert!(adjust("1", "alice", "100").is_ok());
+        assert_eq!(harness.contract.total_ooga(), 5);
+        assert_eq!(harness.contract.total_booga(), 100);
+    }
+
+    #[test]
+    fn test_vesting_grants_respect_supply_cap() {
+        let harness = TestHarness::new();
+        let grant = |address: &str, total: &str| {
+            harness.execute(8, vec![address.to_string(), total.to_string(), "0".to_string(), "10".to_string()])
+        };
+
+        // Supply cap of 10, with 4 OOGA already claimed
+        let _ = harness.execute(0, vec!["0".to_string(), "0".to_string(), "10".to_string()]);
+        for _ in 0..4 {
+            let _ = harness.execute(1, vec!["alice".to_string()]);
+        }
+
+        // A grant that could not vest in full under the cap is rejected
+        let result = grant("bob", "7");
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("OOGA supply cap of 10 reached"));
+        }
+        assert!(harness.contract.vesting_grant("bob").is_none());
+
+        assert!(grant("bob", "6").is_ok());
+        harness.set_height(10);
+        assert!(harness.execute(9, vec!["bob".to_string()]).is_ok());
+        assert_eq!(harness.contract.total_ooga(), 10);
+    }
+
+    #[test]
+    fn test_referral_bonus_overflow_rejected() {
+        let harness = TestHarness::new();
+
+        let _ = harness.execute(0, vec!["0".to_string(), "5".to_string()]);
+        harness.contract.set_ooga_balance("bob", u128::MAX - 1);
+
+        let result = harness.execute(1, vec!["alice".to_string(), "bob".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("balance overflow"));
+        }
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 0);
+        assert_eq!(harness.contract.referrer_of("alice"), None);
+    }
+
+    #[test]
+    fn test_claim_window_bounds_claims() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        // Claiming is open from height 10 through height 20
+        let _ = harness.execute(0, vec![
+            "0".to_string(), "0".to_string(), "0".to_string(), "10".to_string(), "20".to_string(),
+        ]);
+
+        // Before the window
+        harness.set_height(9);
+        let result = harness.execute(1, vec![address.clone()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(error_code(&e), Some(ErrorCode::ClaimWindowClosed));
+            assert!(e.to_string().contains("claim window closed (open from 10 to 20)"));
+        }
+        let response = harness.execute(25, vec![]).unwrap();
+        assert_eq!(extract_u128_at(&response, 0), 10);
+        assert_eq!(extract_u128_at(&response, 1), 20);
+        assert_eq!(payload(&response)[32], 0);
+
+        // Through the window, both bounds included
+        for height in [10, 15, 20] {
+            harness.set_height(height);
+            assert!(harness.execute(1, vec![address.clone()]).is_ok());
+            assert_eq!(payload(&harness.execute(25, vec![]).unwrap())[32], 1);
+        }
+        assert_eq!(harness.contract.ooga_balance_of(&address), 3);
+
+        // Past the window claims are rejected but exchanges still work
+        harness.set_height(21);
+        let result = harness.execute(1, vec![address.clone()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(error_code(&e), Some(ErrorCode::ClaimWindowClosed));
+            assert!(e.to_string().contains("claim window closed"));
+        }
+        assert_eq!(payload(&harness.execute(25, vec![]).unwrap())[32], 0);
+        assert!(harness.execute(2, vec![address.clone()]).is_ok());
+        assert_eq!(harness.contract.ooga_balance_of(&address), 2);
+        assert_eq!(harness.contract.booga_balance_of(&address), 1);
+    }
+
+    #[test]
+    fn test_claim_window_open_by_default() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        let _ = harness.execute(0, vec![]);
+        for height in [0, 1_000_000] {
+            harness.set_height(height);
+            assert!(harness.execute(1, vec![address.clone()]).is_ok());
+        }
+
+        let response = harness.execute(25, vec![]).unwrap();
+        assert_eq!(payload(&response).len(), 33);
+        assert_eq!(extract_u128_at(&response, 0), 0);
+        assert_eq!(extract_u128_at(&response, 1), 0);
+        assert_eq!(payload(&response)[32], 1);
+    }
+
+    #[test]
+    fn test_claim_window_must_not_end_before_start() {
+        let harness = TestHarness::new();
+
+        let result = harness.execute(0, vec![
+            "0".to_string(), "0".to_string(), "0".to_string(), "20".to_string(), "10".to_string(),
+        ]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("claim window ends before it starts"));
+        }
+        assert!(!harness.contract.is_initialized());
+    }
+
+    // Initialized harness where the caller "payer" holds the given OOGA balance
+    fn funded_payer(balance: u128) -> TestHarness {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(19, vec!["0".to_string(), "payer".to_string(), balance.to_string(), "0".to_string()]);
+        harness.set_caller("payer");
+        harness
+    }
+
+    #[test]
+    fn test_multi_transfer_fifty_recipients() {
+        let harness = funded_payer(10_000);
+
+        let mut inputs = Vec::new();
+        for i in 0..50 {
+            inputs.push(format!("recipient-{}", i));
+            inputs.push((i + 1).to_string());
+        }
+        let result = harness.execute(26, inputs);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128_at(&response, 0), 50);
+            assert_eq!(extract_u128_at(&response, 1), 10_000 - 1275);
+        }
+
+        for i in 0..50 {
+            assert_eq!(harness.contract.ooga_balance_of(&format!("recipient-{}", i)), i + 1);
+        }
+        assert_eq!(harness.contract.ooga_balance_of("payer"), 10_000 - 1275);
+        assert_eq!(harness.contract.total_ooga(), 10_000);
+        assert!(harness.contract.check_invariants().passed());
+    }
+
+    #[test]
+    fn test_multi_transfer_exact_balance() {
+        let harness = funded_payer(30);
+
+        let result = harness.execute(26, vec![
+            "alice".to_string(), "10".to_string(),
+            "bob".to_string(), "20".to_string(),
+        ]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128_at(&response, 0), 2);
+            assert_eq!(extract_u128_at(&response, 1), 0);
+        }
+        assert_eq!(harness.contract.ooga_balance_of("payer"), 0);
+
+        // One more unit than the balance is rejected before anything is written
+        let result = harness.execute(26, vec!["alice".to_string(), "1".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("insufficient OOGA balance"));
+        }
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 10);
+    }
+
+    #[test]
+    fn test_multi_transfer_duplicates_accumulate() {
+        let harness = funded_payer(100);
+
+        let result = harness.execute(26, vec![
+            "alice".to_string(), "10".to_string(),
+            "bob".to_string(), "5".to_string(),
+            "alice".to_string(), "15".to_string(),
+        ]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128_at(&response, 0), 2);
+            assert_eq!(extract_u128_at(&response, 1), 70);
+        }
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 25);
+        assert_eq!(harness.contract.ooga_balance_of("bob"), 5);
+    }
+
+    #[test]
+    fn test_multi_transfer_overflow_aborts_batch() {
+        let harness = funded_payer(100);
+        harness.contract.set_ooga_balance("carol", u128::MAX);
+
+        let result = harness.execute(26, vec![
+            "alice".to_string(), "10".to_string(),
+            "carol".to_string(), "1".to_string(),
+        ]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("balance overflow"));
+        }
+        assert_eq!(harness.contract.ooga_balance_of("payer"), 100);
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 0);
+
+        // A missing amount or an empty batch is rejected
+        assert!(harness.execute(26, vec!["alice".to_string()]).is_err());
+        let result = harness.execute(26, vec![]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("no recipients"));
+        }
+    }
+
+    #[test]
+    fn test_holder_rank_with_ties() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+
+        // alice 2, bob 3, carol 2, dave 1
+        for (address, claims) in [("alice", 2), ("bob", 3), ("carol", 2), ("dave", 1)] {
+            for _ in 0..claims {
+                let _ = harness.execute(1, vec![address.to_string()]);
+            }
+        }
+
+        // Ties go to the earlier holder
+        for (address, balance, rank) in [("bob", 3, 1), ("alice", 2, 2), ("carol", 2, 3), ("dave", 1, 4)] {
+            let response = harness.execute(27, vec![address.to_string()]).unwrap();
+            assert_eq!(extract_u128_at(&response, 0), balance);
+            assert_eq!(extract_u128_at(&response, 1), rank);
+            assert_eq!(extract_u128_at(&response, 2), 4);
+        }
+    }
+
+    #[test]
+    fn test_holder_rank_for_zero_balance() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+        let _ = harness.execute(1, vec!["bob".to_string()]);
+
+        // Never held OOGA
+        let response = harness.execute(27, vec!["nobody".to_string()]).unwrap();
+        assert_eq!(payload(&response).len(), 48);
+        assert_eq!(extract_u128_at(&response, 0), 0);
+        assert_eq!(extract_u128_at(&response, 1), 0);
+        assert_eq!(extract_u128_at(&response, 2), 2);
+
+        // Exchanged all OOGA away: still indexed through BOOGA but no longer ranked or counted
+        let _ = harness.execute(2, vec!["bob".to_string()]);
+        let response = harness.execute(27, vec!["bob".to_string()]).unwrap();
+        assert_eq!(extract_u128_at(&response, 1), 0);
+        assert_eq!(extract_u128_at(&response, 2), 1);
+    }
+
+    #[test]
+    fn test_holder_rank_after_exchange() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        for _ in 0..3 {
+            let _ = harness.execute(1, vec!["alice".to_string()]);
+        }
+        for _ in 0..2 {
+            let _ = harness.execute(1, vec!["bob".to_string()]);
+        }
+        assert_eq!(extract_u128_at(&harness.execute(27, vec!["alice".to_string()]).unwrap(), 1), 1);
+        assert_eq!(extract_u128_at(&harness.execute(27, vec!["bob".to_string()]).unwrap(), 1), 2);
+
+        // Two exchanges drop alice below bob
+        let _ = harness.execute(2, vec!["alice".to_string()]);
+        let _ = harness.execute(2, vec!["alice".to_string()]);
+        assert_eq!(extract_u128_at(&harness.execute(27, vec!["alice".to_string()]).unwrap(), 1), 2);
+        assert_eq!(extract_u128_at(&harness.execute(27, vec!["bob".to_string()]).unwrap(), 1), 1);
+    }
+
+    #[test]
+    fn test_exchange_diff_after_repeat_exchanges() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        let _ = harness.execute(0, vec![]);
+        for _ in 0..3 {
+            let _ = harness.execute(1, vec![address.clone()]);
+        }
+        harness.set_height(3);
+        let _ = harness.execute(2, vec![address.clone()]);
+
+        // A second exchange changes existing keys and adds none, the last-active height included
+        harness.set_height(4);
+        let (result, diff) = harness.execute_with_diff(2, vec![address.clone()]);
+        assert!(result.is_ok());
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+        let ooga_key = harness.contract.ooga_balance_key(&address);
+        let booga_key = harness.contract.booga_balance_key(&address);
+        let last_active_key = format!("/last-active/{}", address);
+        diff.assert_only_touched(&[&ooga_key, &booga_key, "/total-ooga", "/total-booga", &last_active_key]);
+        assert_eq!(diff.changed[&booga_key], (1u128.to_le_bytes().to_vec(), 2u128.to_le_bytes().to_vec()));
+        assert_eq!(diff.new_u128(&last_active_key), Some(4));
+    }
+
+    #[test]
+    fn test_claim_diff_records_last_active() {
+        let harness = TestHarness::new();
+        let address = test_address();
+        let _ = harness.execute(0, vec![]);
+
+        // The first claim adds the address's last-active height, later ones move it
+        harness.set_height(6);
+        let (result, diff) = harness.execute_with_diff(1, vec![address.clone()]);
+        assert!(result.is_ok());
+        let last_active_key = format!("/last-active/{}", address);
+        assert_eq!(diff.added.get(&last_active_key), Some(&6u128.to_le_bytes().to_vec()));
+
+        harness.set_height(9);
+        let (result, diff) = harness.execute_with_diff(1, vec![address.clone()]);
+        assert!(result.is_ok());
+        assert_eq!(diff.changed[&last_active_key], (6u128.to_le_bytes().to_vec(), 9u128.to_le_bytes().to_vec()));
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected writes to")]
+    fn test_storage_diff_flags_extra_writes() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        let _ = harness.execute(0, vec![]);
+
+        // The first claim also indexes the holder and records the address as active,
+        // which this expectation leaves out
+        harness.set_height(2);
+        let (_, diff) = harness.execute_with_diff(1, vec![address.clone()]);
+        diff.assert_only_touched(&[&harness.contract.ooga_balance_key(&address), "/total-ooga"]);
+    }
+
+    const PAYMENT_TOKEN: AlkaneId = AlkaneId { block: 2, tx: 7 };
+
+    // Initialized harness charging 10 units of PAYMENT_TOKEN per claim
+    fn paid_claim_harness() -> TestHarness {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(28, vec!["2".to_string(), "7".to_string(), "10".to_string()]);
+        harness
+    }
+
+    #[test]
+    fn test_paid_claim_exact_payment() {
+        let harness = paid_claim_harness();
+
+        let result = harness.execute_with_alkanes(1, vec!["alice".to_string()], vec![(PAYMENT_TOKEN, 10)]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128_at(&response, 0), 10);
+            assert_eq!(extract_u128_at(&response, 1), 0);
+            assert!(response.alkanes.is_empty());
+        }
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 1);
+    }
+
+    #[test]
+    fn test_paid_claim_refunds_overpayment() {
+        let harness = paid_claim_harness();
+
+        let result = harness.execute_with_alkanes(1, vec!["alice".to_string()], vec![(PAYMENT_TOKEN, 30)]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128_at(&response, 0), 10);
+            assert_eq!(extract_u128_at(&response, 1), 20);
+            assert_eq!(response.alkanes, vec![(PAYMENT_TOKEN, 20)]);
+        }
+    }
+
+    #[test]
+    fn test_paid_claim_refund_across_split_payment() {
+        let harness = paid_claim_harness();
+        let other = AlkaneId { block: 4, tx: 1 };
+
+        // The price is taken from the first entry and the rest of the second;
+        // unrelated alkanes are returned untouched
+        let result = harness.execute_with_alkanes(1, vec!["alice".to_string()], vec![
+            (PAYMENT_TOKEN, 6),
+            (other, 3),
+            (PAYMENT_TOKEN, 9),
+        ]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert_eq!(extract_u128_at(&response, 0), 10);
+            assert_eq!(extract_u128_at(&response, 1), 5);
+            assert_eq!(response.alkanes, vec![(other, 3), (PAYMENT_TOKEN, 5)]);
+        }
+    }
+
+    #[test]
+    fn test_paid_claim_underpayment_rejected() {
+        let harness = paid_claim_harness();
+
+        let result = harness.execute_with_alkanes(1, vec!["alice".to_string()], vec![
+            (PAYMENT_TOKEN, 4),
+            (AlkaneId { block: 4, tx: 1 }, 100),
+        ]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("insufficient claim payment: received 4 of 10"));
+        }
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 0);
+        assert_eq!(harness.contract.total_ooga(), 0);
+    }
+
+    #[test]
+    fn test_free_claims_forward_incoming_alkanes() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+
+        let result = harness.execute_with_alkanes(1, vec!["alice".to_string()], vec![(PAYMENT_TOKEN, 10)]);
+        assert!(result.is_ok());
+        if let Ok(response) = result {
+            assert!(payload(&response).is_empty());
+            assert_eq!(response.alkanes, vec![(PAYMENT_TOKEN, 10)]);
+        }
+    }
+
+    #[test]
+    fn test_claim_price_requires_owner() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+
+        harness.set_caller("mallory");
+        let result = harness.execute(28, vec!["2".to_string(), "7".to_string(), "10".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("caller is not the owner"));
+        }
+
+        harness.set_caller(DEFAULT_CALLER);
+        assert!(harness.execute(28, vec!["2".to_string(), "7".to_string(), "10".to_string()]).is_ok());
+        let response = harness.execute(29, vec![]).unwrap();
+        assert_eq!(extract_u128_at(&response, 0), 2);
+        assert_eq!(extract_u128_at(&response, 1), 7);
+        assert_eq!(extract_u128_at(&response, 2), 10);
+    }
+
+    #[test]
+    fn test_zero_balances_leave_storage_and_holder_index() {
+        let harness = TestHarness::new();
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(1, vec![bob.clone()]);
+        let _ = harness.execute(1, vec![alice.clone()]);
+        assert_eq!(harness.contract.holder_count(), 2);
+
+        // Exchanging everything away deletes the OOGA balance; alice still holds BOOGA
+        let _ = harness.execute(2, vec![alice.clone()]);
+        assert!(!snapshot_storage().contains_key(&harness.contract.ooga_balance_key(&alice)));
+        assert!(harness.contract.is_holder(&alice));
+
+        // Moving the BOOGA away too removes alice from the index
+        harness.set_caller("alice");
+        let _ = harness.execute(14, vec![bob.clone(), "1".to_string()]);
+        harness.set_caller("bob");
+        let result = harness.execute(16, vec![alice.clone(), bob.clone(), "1".to_string()]);
+        assert!(result.is_ok());
+        let storage = snapshot_storage();
+        assert!(!storage.contains_key(&harness.contract.booga_balance_key(&alice)));
+        assert!(!storage.contains_key(&harness.contract.holder_position_key(&alice)));
+        assert_eq!(harness.contract.holder_count(), 1);
+        assert!(!harness.contract.is_holder(&alice));
+        assert_eq!(harness.contract.holders(0, 10), vec![bob.clone()]);
+
+        // Claiming again re-adds alice exactly once
+        let _ = harness.execute(1, vec![alice.clone()]);
+        let _ = harness.execute(1, vec![alice.clone()]);
+        assert_eq!(harness.contract.holder_count(), 2);
+        assert_eq!(harness.contract.holders(0, 10), vec![bob.clone(), alice.clone()]);
+        assert!(harness.contract.check_invariants().passed());
+    }
+
+    #[test]
+    fn test_holder_removal_swaps_last_entry() {
+        let harness = TestHarness::new();
+
+        // alice, bob and carol hold 1 OOGA each
+        let _ = harness.execute(0, vec![]);
+        for address in ["alice", "bob", "carol"] {
+            let _ = harness.execute(1, vec![address.to_string()]);
+        }
+
+        // Emptying alice moves carol into the first slot
+        let _ = harness.execute(19, vec!["0".to_string(), "alice".to_string(), "0".to_string(), "0".to_string()]);
+        assert_eq!(harness.contract.holders(0, 10), vec!["carol".to_string(), "bob".to_string()]);
+        assert!(harness.contract.is_holder("carol"));
+        assert!(!harness.contract.is_holder("alice"));
+
+        // Every enumerated holder has a nonzero balance, and the moved entry can still be removed
+        for holder in harness.contract.holders(0, 10) {
+            assert!(harness.contract.ooga_balance_of(&holder) > 0 || harness.contract.booga_balance_of(&holder) > 0);
+        }
+        let _ = harness.execute(19, vec!["0".to_string(), "carol".to_string(), "0".to_string(), "0".to_string()]);
+        assert_eq!(harness.contract.holders(0, 10), vec!["bob".to_string()]);
+        assert!(harness.contract.check_invariants().passed());
+    }
+
+    #[test]
+    fn test_last_active_tracks_mutations() {
+        let harness = TestHarness::new();
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+
+        let _ = harness.execute(0, vec![]);
+
+        // First activity records the height of that first operation
+        harness.set_height(5);
+        let _ = harness.execute(1, vec![alice.clone()]);
+        let response = harness.execute(30, vec![alice.clone()]).unwrap();
+        assert_eq!(extract_u128_at(&response, 0), 5);
+        assert_eq!(extract_u128_at(&response, 1), 5);
+        assert_eq!(payload(&response)[32], 1);
+
+        // Queries do not count as activity
+        harness.set_height(12);
+        let response = harness.execute(30, vec![alice.clone()]).unwrap();
+        assert_eq!(extract_u128_at(&response, 0), 5);
+        assert_eq!(extract_u128_at(&response, 1), 12);
+
+        // An exchange at a later height moves the timestamp forward
+        let _ = harness.execute(1, vec![alice.clone()]);
+        harness.set_height(20);
+        let _ = harness.execute(2, vec![alice.clone()]);
+        assert_eq!(harness.contract.activity(&alice, 20).last_active, 20);
+
+        // A transfer marks the spender, the owner and the recipient
+        let _ = harness.execute(1, vec![alice.clone()]);
+        harness.set_caller("alice");
+        let _ = harness.execute(11, vec![bob.clone(), "1".to_string()]);
+        harness.set_height(31);
+        harness.set_caller("bob");
+        let _ = harness.execute(13, vec![alice.clone(), "carol".to_string(), "1".to_string()]);
+        for address in ["alice", "bob", "carol"] {
+            assert_eq!(harness.contract.activity(address, 31).last_active, 31);
+        }
+    }
+
+    #[test]
+    fn test_last_active_unseen_address_is_flagged() {
+        let harness = TestHarness::new();
+
+        // Activity at height 0 is distinguishable from never being seen
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+        harness.set_height(3);
+
+        let seen = harness.execute(30, vec!["alice".to_string()]).unwrap();
+        let unseen = harness.execute(30, vec!["nobody".to_string()]).unwrap();
+        assert_eq!(payload(&seen).len(), 33);
+        assert_eq!(extract_u128_at(&seen, 0), 0);
+        assert_eq!(payload(&seen)[32], 1);
+        assert_eq!(extract_u128_at(&unseen, 0), 0);
+        assert_eq!(extract_u128_at(&unseen, 1), 3);
+        assert_eq!(payload(&unseen)[32], 0);
+    }
+
+    #[test]
+    fn test_failed_operations_leave_last_active() {
+        let harness = TestHarness::new();
+        let address = test_address();
+
+        let _ = harness.execute(0, vec![]);
+        harness.set_height(4);
+        let _ = harness.execute(1, vec![address.clone()]);
+        let _ = harness.execute(2, vec![address.clone()]);
+
+        // Exchanging with no OOGA left fails and keeps the earlier height
+        harness.set_height(9);
+        assert!(harness.execute(2, vec![address.clone()]).is_err());
+        assert_eq!(harness.contract.activity(&address, 9).last_active, 4);
+
+        // So does a transfer without an allowance, for every address involved
+        harness.set_caller("mallory");
+        assert!(harness.execute(16, vec![address.clone(), "bob".to_string(), "1".to_string()]).is_err());
+        assert_eq!(harness.contract.activity(&address, 9).last_active, 4);
+        assert!(!harness.contract.activity("mallory", 9).seen);
+        assert!(!harness.contract.activity("bob", 9).seen);
+    }
+
+    #[test]
+    fn test_opcode_table_covers_every_code() {
+        // Codes are contiguous from 0 and each one decodes to its own variant
+        for (index, opcode) in Opcode::ALL.iter().enumerate() {
+            assert_eq!(opcode.code(), index as u128);
+            assert_eq!(Opcode::from_code(opcode.code()).unwrap(), *opcode);
+        }
+
+        let result = Opcode::from_code(Opcode::ALL.len() as u128);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("unrecognized opcode"));
+        }
+    }
+
+    // Code of an error returned by `execute`: the error frame's code when
+    // responses are framed, or the contract's own error with legacy responses
+    fn error_code(error: &anyhow::Error) -> Option<ErrorCode> {
+        match error.downcast_ref::<ErrorFrame>() {
+            Some(frame) => frame.code(),
+            None => Some(ErrorCode::of(error)),
+        }
+    }
+
+    // Decode an error frame into its code and message
+    #[cfg(not(feature = "legacy-responses"))]
+    fn error_frame(response: &CallResponse) -> (Option<ErrorCode>, String) {
+        let error = decode_response(&response.data).unwrap_err();
+        let frame = error.downcast_ref::<ErrorFrame>().unwrap();
+        (frame.code(), frame.message.clone())
+    }
+
+    #[cfg(not(feature = "legacy-responses"))]
+    #[test]
+    fn test_success_frames_for_every_opcode() {
+        let harness = TestHarness::new();
+        let owner = DEFAULT_CALLER.to_string();
+        let calls: Vec<(Opcode, Vec<&str>)> = vec![
+            // Bootstrap batches are only accepted before init
+            (Opcode::Bootstrap, vec!["zed", "1", "0"]),
+            (Opcode::Initialize, vec!["10", "1"]),
+            (Opcode::Claim, vec!["alice", "bob"]),
+            (Opcode::Exchange, vec!["bob"]),
+            (Opcode::OogaBalance, vec!["alice"]),
+            (Opcode::BoogaBalance, vec!["bob"]),
+            (Opcode::TotalOoga, vec![]),
+            (Opcode::TotalBooga, vec![]),
+            (Opcode::ClaimCap, vec![]),
+            (Opcode::CreateVestingGrant, vec!["carol", "10", "0", "10"]),
+            (Opcode::ClaimVested, vec!["carol"]),
+            (Opcode::VestingGrant, vec!["carol"]),
+            (Opcode::ApproveOoga, vec![&owner, "1"]),
+            (Opcode::OogaAllowance, vec!["alice", &owner]),
+            (Opcode::TransferOogaFrom, vec!["alice", "alice", "1"]),
+            (Opcode::ApproveBooga, vec![&owner, "1"]),
+            (Opcode::BoogaAllowance, vec!["bob", &owner]),
+            (Opcode::TransferBoogaFrom, vec!["bob", "bob", "1"]),
+            (Opcode::OogaSpendable, vec![&owner, "alice"]),
+            (Opcode::BoogaSpendable, vec![&owner, "alice"]),
+            (Opcode::AdjustBalance, vec!["0", "dave", "5", "1"]),
+            (Opcode::EventsSince, vec!["0", "2"]),
+            (Opcode::CheckInvariants, vec![]),
+            (Opcode::AddressEvents, vec!["alice", "0", "2"]),
+            (Opcode::Referrer, vec!["alice"]),
+            (Opcode::ReferralEarnings, vec!["bob"]),
+            (Opcode::ClaimWindow, vec![]),
+            (Opcode::TransferMany, vec!["erin", "1"]),
+            (Opcode::HolderRank, vec!["alice"]),
+            (Opcode::SetClaimPrice, vec!["2", "7", "0"]),
+            (Opcode::ClaimPrice, vec![]),
+            (Opcode::LastActive, vec!["alice"]),
+            (Opcode::QuoteExchange, vec!["alice", "1"]),
+            (Opcode::ClaimReward, vec![]),
+            (Opcode::OfferSwap, vec!["0", "1", "1", "10"]),
+            (Opcode::Swap, vec!["dave", "1", "1"]),
+            (Opcode::SwapOffer, vec!["dave"]),
+        ];
+        assert_eq!(calls.len(), Opcode::ALL.len());
+
+        harness.set_height(5);
+        for (opcode, inputs) in calls {
+            // Payments and swaps move the caller's balance, so send from funded
+            // addresses; they approve the owner to spend on their behalf
+            harness.set_caller(match opcode {
+                Opcode::ApproveOoga | Opcode::TransferMany => "alice",
+                Opcode::ApproveBooga => "bob",
+                Opcode::OfferSwap => "dave",
+                Opcode::Swap => "bob",
+                _ => DEFAULT_CALLER,
+            });
+            let inputs = inputs.into_iter().map(str::to_string).collect();
+            let before = snapshot_storage();
+            let response = harness.execute_framed(opcode.code(), inputs).unwrap();
+            assert_eq!(response.data[0], STATUS_OK, "{:?} failed: {:?}", opcode, error_frame(&response));
+            assert_eq!(payload(&response), &response.data[1..]);
+            // The table's access column matches what the opcode actually does
+            assert_eq!(snapshot_storage() != before, opcode.is_mutating(), "{:?}", opcode);
+        }
+    }
+
+    #[test]
+    fn test_error_code_table_round_trips() {
+        for code in ErrorCode::ALL {
+            assert_eq!(ErrorCode::from_status(*code as u8), Some(*code));
+            assert_eq!(ErrorCode::from_name(code.name()), Some(*code));
+        }
+        assert_eq!(ErrorCode::from_name("claim_window_closed"), Some(ErrorCode::ClaimWindowClosed));
+        assert_eq!(ErrorCode::from_status(STATUS_OK), None);
+        assert_eq!(ErrorCode::from_name("ClaimWindowClosed"), None);
+    }
+
+    #[cfg(not(feature = "legacy-responses"))]
+    #[test]
+    fn test_error_frames_carry_code_and_message() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+
+        let cases: Vec<(u128, Vec<&str>, ErrorCode, &str)> = vec![
+            (99, vec![], ErrorCode::UnrecognizedOpcode, "unrecognized opcode"),
+            (3, vec![], ErrorCode::InvalidInput, ""),
+            (19, vec!["9", "alice", "1", "0"], ErrorCode::InvalidInput, ""),
+            (0, vec![], ErrorCode::AlreadyInitialized, "contract already initialized"),
+            (2, vec!["alice"], ErrorCode::Failed, "insufficient"),
+        ];
+        for (opcode, inputs, code, message) in cases {
+            let inputs = inputs.into_iter().map(str::to_string).collect();
+            let response = harness.execute_framed(opcode, inputs).unwrap();
+            let (actual_code, actual_message) = error_frame(&response);
+            assert_eq!(actual_code, Some(code), "opcode {}: {}", opcode, actual_message);
+            assert!(actual_message.contains(message), "opcode {}: {}", opcode, actual_message);
+        }
+
+        harness.set_caller("mallory");
+        let response = harness.execute_framed(28, vec!["2".to_string(), "7".to_string(), "10".to_string()]).unwrap();
+        assert_eq!(error_frame(&response), (Some(ErrorCode::NotOwner), "caller is not the owner".to_string()));
+    }
+
+    #[cfg(not(feature = "legacy-responses"))]
+    #[test]
+    fn test_error_frame_discards_writes_and_forwards_alkanes() {
+        let harness = paid_claim_harness();
+        let before = harness.contract.total_ooga();
+
+        let response = harness.execute_framed_with_alkanes(1, vec!["alice".to_string()], vec![(PAYMENT_TOKEN, 4)]).unwrap();
+        let (code, message) = error_frame(&response);
+        assert_eq!(code, Some(ErrorCode::Failed));
+        assert_eq!(message, "insufficient claim payment: received 4 of 10");
+        assert_eq!(response.alkanes, vec![(PAYMENT_TOKEN, 4)]);
+        assert_eq!(harness.contract.total_ooga(), before);
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 0);
+    }
+
+    #[cfg(not(feature = "legacy-responses"))]
+    #[test]
+    fn test_execute_turns_error_frames_into_errors() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+
+        let result = harness.execute(2, vec!["alice".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(e.downcast_ref::<ErrorFrame>().unwrap().code(), Some(ErrorCode::Failed));
+        }
+
+        let result = decode_response(&[]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("empty response frame"));
+        }
+    }
+
+    #[test]
+    fn test_encode_legacy_passes_results_through() {
+        let transfers = vec![(AlkaneId { block: 2, tx: 7 }, 3)];
+        let output = encode_legacy(Ok(Output { data: vec![1, 2], alkanes: Some(transfers.clone()) })).unwrap();
+        assert_eq!(output.data, vec![1, 2]);
+        assert_eq!(output.alkanes, Some(transfers));
+
+        // The framed encoding of the same result, for contrast
+        assert_eq!(encode_response(Ok(vec![1u8, 2].into_output())).data, vec![0, 1, 2]);
+
+        // Errors stay failed calls carrying the contract's own error
+        let error = encode_legacy(Err(LimitOutOfRange { limit: 0, max: 5 }.into())).unwrap_err();
+        assert!(error.downcast_ref::<ErrorFrame>().is_none());
+        assert_eq!(ErrorCode::of(&error), ErrorCode::LimitOutOfRange);
+        assert_eq!(error.to_string(), "limit 0 out of range 1..=5");
+    }
+
+    #[cfg(feature = "legacy-responses")]
+    #[test]
+    fn test_legacy_responses_are_unframed() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+
+        // Queries return the bare payload and other opcodes return empty data
+        let response = harness.execute(3, vec!["alice".to_string()]).unwrap();
+        assert_eq!(response.data, 1u128.to_le_bytes().to_vec());
+        assert_eq!(payload(&response), &response.data[..]);
+        assert_eq!(extract_u128(&response), 1);
+        harness.set_caller("alice");
+        let response = harness.execute(11, vec!["bob".to_string(), "1".to_string()]).unwrap();
+        assert!(response.data.is_empty());
+
+        // A failing opcode fails the call, with no frame, and leaves storage untouched
+        let before = snapshot_storage();
+        let error = harness.execute_framed(2, vec!["bob".to_string()]).unwrap_err();
+        assert!(error.downcast_ref::<ErrorFrame>().is_none());
+        assert_eq!(error_code(&error), Some(ErrorCode::Failed));
+        assert!(error.to_string().contains("insufficient"));
+        assert_eq!(snapshot_storage(), before);
+    }
+
+    // Path of a scenario fixture shipped under tests/scenarios
+    fn scenario_fixture(name: &str) -> String {
+        format!("{}/tests/scenarios/{}.json", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    #[test]
+    fn test_scenario_fixtures_pass() {
+        for name in ["complete_flow", "error_handling"] {
+            let harness = TestHarness::new();
+            let report = harness.run_scenario(&scenario_fixture(name)).unwrap();
+            report.assert_passed();
+            assert!(report.steps_run > 0);
+        }
+    }
+
+    #[test]
+    fn test_scenario_reports_first_failing_step_with_diff() {
+        let harness = TestHarness::new();
+        let report = harness.run_scenario(r#"{
+            "name": "wrong expectation",
+            "steps": [
+                { "opcode": 0 },
+                { "caller": "alice", "opcode": 1, "inputs": ["alice"] },
+                { "opcode": 2, "inputs": ["alice"], "expect": "error" },
+                { "opcode": 99 }
+            ]
+        }"#).unwrap();
+
+        assert!(!report.passed());
+        assert_eq!(report.steps_run, 3);
+        let failure = report.failure.unwrap();
+        assert_eq!(failure.step, Some(2));
+        assert!(failure.reason.contains("expected an error"));
+        assert_eq!(failure.diff.new_u128(&harness.contract.booga_balance_key("alice")), Some(1));
+    }
+
+    #[test]
+    fn test_scenario_checks_codes_payloads_and_balances() {
+        let harness = TestHarness::new();
+        let report = harness.run_scenario(r#"{
+            "steps": [
+                { "opcode": 0 },
+                { "opcode": 99, "expect": "error", "error_code": "invalid_input" }
+            ]
+        }"#).unwrap();
+        assert_eq!(report.failure.unwrap().reason, "expected error code InvalidInput, got UnrecognizedOpcode: unrecognized opcode");
+
+        let harness = TestHarness::new();
+        let report = harness.run_scenario(r#"{
+            "steps": [{ "opcode": 0 }, { "opcode": 1, "inputs": ["bob"] }, { "opcode": 3, "inputs": ["bob"], "payload": ["2"] }]
+        }"#).unwrap();
+        assert!(report.failure.unwrap().reason.starts_with("expected payload 02"));
+
+        let harness = TestHarness::new();
+        let report = harness.run_scenario(r#"{
+            "steps": [{ "opcode": 0 }, { "height": 4, "opcode": 1, "inputs": ["bob"] }],
+            "balances": { "bob": { "ooga": 2 } }
+        }"#).unwrap();
+        let failure = report.failure.unwrap();
+        assert_eq!(failure.step, None);
+        assert_eq!(failure.reason, "expected bob to hold 2 OOGA and 0 BOOGA, found 1 OOGA and 0 BOOGA");
+        assert_eq!(failure.diff.new_u128(&harness.contract.ooga_balance_key("bob")), Some(1));
+        assert_eq!(harness.contract.activity("bob", 4).last_active, 4);
+    }
+
+    #[test]
+    fn test_scenario_rejects_malformed_fixtures() {
+        let harness = TestHarness::new();
+        for (source, message) in [
+            (r#"{ "name": "no steps" }"#, "scenario has no steps list"),
+            (r#"{ "steps": [{ "inputs": [] }] }"#, "step 0: missing opcode"),
+            (r#"{ "steps": [{ "opcode": 1, "expect": "maybe" }] }"#, "expect must be"),
+            (r#"{ "steps": [{ "opcode": 1, "error_code": "oops" }] }"#, "unknown error code"),
+            (r#"{ "steps": [{ "opcode": 1, "payload": "abc" }] }"#, "invalid hex payload"),
+        ] {
+            let result = harness.run_scenario(source);
+            assert!(result.is_err());
+            if let Err(e) = result {
+                assert!(e.to_string().contains(message), "{}: {}", source, e);
+            }
+        }
+
+        let result = harness.run_scenario("missing/scenario.json");
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("failed to read scenario"));
+        }
+    }
+
+    #[test]
+    fn test_views_match_query_opcodes() {
+        let harness = TestHarness::new();
+        harness.run_scenario(r#"{
+            "steps": [
+                { "opcode": 0, "inputs": ["0", "0", "100"] },
+                { "height": 3, "opcode": 1, "inputs": ["alice"] },
+                { "height": 4, "opcode": 1, "inputs": ["alice"] },
+                { "height": 5, "opcode": 1, "inputs": ["bob"] },
+                { "height": 6, "opcode": 2, "inputs": ["alice"] }
+            ]
+        }"#).unwrap().assert_passed();
+
+        for address in ["alice", "bob", "carol"] {
+            let view = harness.contract.account_view(address);
+            let last_active = harness.execute(30, vec![address.to_string()]).unwrap();
+            assert_eq!(view.address, address);
+            assert_eq!(view.ooga, extract_u128(&harness.execute(3, vec![address.to_string()]).unwrap()));
+            assert_eq!(view.booga, extract_u128(&harness.execute(4, vec![address.to_string()]).unwrap()));
+            assert_eq!(view.last_active.is_some(), payload(&last_active)[32] == 1);
+            assert_eq!(view.last_active.unwrap_or(0) as u128, extract_u128(&last_active));
+        }
+        assert_eq!(harness.contract.account_view("alice").last_active, Some(6));
+        assert_eq!(harness.contract.account_view("carol").last_active, None);
+
+        // bob pays his only OOGA to carol, so the holders change without their number changing
+        harness.set_caller("bob");
+        assert!(harness.execute(26, vec!["carol".to_string(), "1".to_string()]).is_ok());
+        let holding = ["alice", "bob", "carol"].iter()
+            .map(|address| harness.contract.account_view(address))
+            .filter(|view| view.ooga > 0 || view.booga > 0)
+            .count() as u128;
+        assert_eq!(holding, 2);
+
+        let supply = harness.contract.supply_view();
+        assert_eq!(supply.total_ooga, extract_u128(&harness.execute(5, vec![]).unwrap()));
+        assert_eq!(supply.total_booga, extract_u128(&harness.execute(6, vec![]).unwrap()));
+        assert_eq!(supply.holders, holding);
+        assert_eq!(supply, SupplyView { total_ooga: 2, total_booga: 1, max_supply: 100, holders: 2 });
+
+        // Emptying a holder is reflected too
+        harness.set_caller("carol");
+        assert!(harness.execute(26, vec!["alice".to_string(), "1".to_string()]).is_ok());
+        assert_eq!(harness.contract.supply_view().holders, 1);
+    }
+
+    #[cfg(feature = "views")]
+    #[test]
+    fn test_views_serialize() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+
+        let account = serde_json::to_value(harness.contract.account_view("alice")).unwrap();
+        assert_eq!(account["address"], "alice");
+        assert_eq!(account["ooga"], 1);
+        assert_eq!(account["last_active"], 0);
+        assert!(account.get("frozen").is_none());
+
+        let supply = serde_json::to_value(harness.contract.supply_view()).unwrap();
+        assert_eq!(supply["total_ooga"], 1);
+        assert_eq!(supply["holders"], 1);
+    }
+
+    // Bootstrap inputs for accounts `first..last`, holding i + 1 OOGA and 2i BOOGA each
+    fn bootstrap_inputs(first: u128, last: u128) -> Vec<String> {
+        (first..last)
+            .flat_map(|i| vec![format!("account-{}", i), (i + 1).to_string(), (2 * i).to_string()])
+            .collect()
+    }
+
+    #[test]
+    fn test_bootstrap_in_two_phases() {
+        let harness = TestHarness::new();
+
+        let response = harness.execute(31, bootstrap_inputs(0, 15)).unwrap();
+        let ooga: u128 = (0..15).map(|i| i + 1).sum();
+        let booga: u128 = (0..15).map(|i| 2 * i).sum();
+        assert_eq!((extract_u128_at(&response, 0), extract_u128_at(&response, 1)), (ooga, booga));
+        assert!(harness.contract.is_bootstrapping());
+        assert!(!harness.contract.is_initialized());
+
+        let response = harness.execute(31, bootstrap_inputs(15, 30)).unwrap();
+        let ooga: u128 = (0..30).map(|i| i + 1).sum();
+        let booga: u128 = (0..30).map(|i| 2 * i).sum();
+        assert_eq!((extract_u128_at(&response, 0), extract_u128_at(&response, 1)), (ooga, booga));
+
+        // Init finalizes the bootstrap without zeroing the loaded totals
+        let result = harness.execute(0, vec!["0".to_string(), "0".to_string(), "1000".to_string()]);
+        assert!(result.is_ok());
+        assert!(!harness.contract.is_bootstrapping());
+        assert_eq!(extract_u128(&harness.execute(5, vec![]).unwrap()), ooga);
+        assert_eq!(extract_u128(&harness.execute(6, vec![]).unwrap()), booga);
+        for i in 0..30u128 {
+            let address = format!("account-{}", i);
+            assert_eq!(harness.contract.ooga_balance_of(&address), i + 1);
+            assert_eq!(harness.contract.booga_balance_of(&address), 2 * i);
+        }
+        assert_eq!(harness.contract.holder_count(), 30);
+        assert_eq!(payload(&harness.execute(21, vec![]).unwrap())[96], 1);
+    }
+
+    #[test]
+    fn test_bootstrap_blocks_mutations_until_finalized() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(31, vec!["alice".to_string(), "5".to_string(), "0".to_string()]);
+
+        for (opcode, inputs) in [
+            (1, vec!["bob"]),
+            (2, vec!["alice"]),
+            (11, vec!["bob", "1"]),
+            (19, vec!["0", "alice", "1", "0"]),
+            (26, vec!["bob", "1"]),
+            (28, vec!["2", "7", "10"]),
+        ] {
+            let result = harness.execute(opcode, inputs.into_iter().map(str::to_string).collect());
+            assert!(result.is_err());
+            if let Err(e) = result {
+                assert!(e.to_string().contains("bootstrap in progress"), "opcode {}: {}", opcode, e);
+            }
+        }
+
+        // Queries still answer from the loaded balances
+        assert_eq!(extract_u128(&harness.execute(3, vec!["alice".to_string()]).unwrap()), 5);
+
+        let _ = harness.execute(0, vec![]);
+        assert!(harness.execute(1, vec!["bob".to_string()]).is_ok());
+        assert!(harness.execute(2, vec!["alice".to_string()]).is_ok());
+    }
+
+    #[test]
+    fn test_bootstrap_access_and_limits() {
+        let harness = TestHarness::new();
+
+        let result = harness.execute(31, bootstrap_inputs(0, 26));
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("bootstrap batch of 26 entries exceeds limit of 25"));
+        }
+        let result = harness.execute(31, vec!["alice".to_string(), "5".to_string()]);
+        assert!(result.is_err());
+        assert!(!harness.contract.is_bootstrapping());
+
+        // The first batch pins the owner for later batches and the finalizing init
+        let _ = harness.execute(31, vec!["alice".to_string(), "5".to_string(), "0".to_string()]);
+        harness.set_caller("mallory");
+        let result = harness.execute(31, vec!["mallory".to_string(), "5".to_string(), "0".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("caller is not the owner"));
+        }
+        assert!(harness.execute(0, vec![]).is_err());
+        harness.set_caller(DEFAULT_CALLER);
+
+        let result = harness.execute(31, vec!["bob".to_string(), u128::MAX.to_string(), "0".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("supply overflow"));
+        }
+        let result = harness.execute(0, vec!["0".to_string(), "0".to_string(), "4".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("bootstrapped OOGA supply of 5 exceeds cap of 4"));
+        }
+
+        assert!(harness.execute(0, vec![]).is_ok());
+        assert_eq!(harness.contract.owner(), DEFAULT_CALLER);
+        let result = harness.execute(31, vec!["bob".to_string(), "1".to_string(), "0".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("contract already initialized"));
+        }
+    }
+
+    #[test]
+    fn test_exchange_matches_its_quote() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        for _ in 0..6 {
+            let _ = harness.execute(1, vec!["alice".to_string()]);
+        }
+
+        for amount in [1u128, 3, 5] {
+            let inputs = vec!["alice".to_string(), amount.to_string()];
+
+            // Quoting is a pure query
+            let (quote, diff) = harness.execute_with_diff(32, inputs.clone());
+            assert!(diff.is_empty());
+            let quote = quote.unwrap();
+            let plan = harness.contract.quote_exchange("alice", amount);
+            assert_eq!(payload(&quote), plan.encode().as_slice());
+
+            let (result, diff) = harness.execute_with_diff(2, inputs);
+            if plan.is_ok() {
+                assert!(result.is_ok());
+                assert_eq!(extract_u128_at(&quote, 0), amount);
+                assert_eq!(extract_u128_at(&quote, 1), amount);
+                assert_eq!(extract_u128_at(&quote, 2), 0);
+                assert_eq!(payload(&quote)[48], 1);
+                assert_eq!(harness.contract.exchange_balances("alice"), plan.balances);
+                assert_eq!(diff.new_u128(&harness.contract.booga_balance_key("alice")), Some(plan.balances.booga_balance));
+            } else {
+                // The 5 unit exchange exceeds the 2 OOGA left after the first two
+                assert_eq!(amount, 5);
+                assert_eq!(payload(&quote)[48], 0);
+                assert_eq!(&payload(&quote)[49..], b"insufficient OOGA balance");
+                let error = result.unwrap_err().to_string();
+                assert_eq!(Some(error), plan.failure);
+                assert!(diff.is_empty());
+            }
+        }
+        assert_eq!(harness.contract.exchange_balances("alice"), ExchangeBalances {
+            ooga_balance: 2,
+            booga_balance: 4,
+            total_ooga: 2,
+            total_booga: 4,
+        });
+    }
+
+    #[test]
+    fn test_exchange_defaults_to_one_unit() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+
+        let quote = harness.execute(32, vec!["alice".to_string()]).unwrap();
+        assert_eq!(extract_u128_at(&quote, 0), 1);
+        let _ = harness.execute(2, vec!["alice".to_string()]);
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 1);
+
+        // A zero amount is rejected while decoding the inputs, for quotes as well
+        let result = harness.execute(32, vec!["alice".to_string(), "0".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(error_code(&e), Some(ErrorCode::ZeroAmount));
+        }
+        let result = harness.execute(2, vec!["alice".to_string(), "0".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(error_code(&e), Some(ErrorCode::ZeroAmount));
+            assert_eq!(e.to_string(), "amount must be nonzero");
+        }
+    }
+
+    #[test]
+    fn test_compute_exchange_rejects_overflow() {
+        let balances = ExchangeBalances {
+            ooga_balance: 5,
+            booga_balance: u128::MAX,
+            total_ooga: 5,
+            total_booga: u128::MAX,
+        };
+        let plan = compute_exchange(1, balances);
+        assert!(!plan.is_ok());
+        assert_eq!(plan.failure.as_deref(), Some("balance overflow"));
+        assert_eq!((plan.ooga_debited, plan.booga_credited, plan.fee), (0, 0, 0));
+        assert_eq!(plan.balances, balances);
+
+        let plan = compute_exchange(1, ExchangeBalances { booga_balance: 0, ..balances });
+        assert_eq!(plan.failure.as_deref(), Some("supply overflow"));
+    }
+
+    // Initialize at `height` with the given base reward and halving interval
+    fn reward_harness(height: u64, base_reward: u128, halving_interval: u64) -> TestHarness {
+        let harness = TestHarness::new();
+        harness.set_height(height);
+        let mut inputs = vec!["0".to_string(); 5];
+        inputs.extend([base_reward.to_string(), halving_interval.to_string()]);
+        assert!(harness.execute(0, inputs).is_ok());
+        harness
+    }
+
+    #[test]
+    fn test_claim_reward_halves_across_boundaries() {
+        let harness = reward_harness(100, 8, 10);
+
+        for (height, reward, next_halving) in [(100, 8, 110), (109, 8, 110), (110, 4, 120), (119, 4, 120), (120, 2, 130)] {
+            harness.set_height(height);
+            let response = harness.execute(33, vec![]).unwrap();
+            assert_eq!(extract_u128_at(&response, 0), reward, "height {}", height);
+            assert_eq!(extract_u128_at(&response, 1), next_halving, "height {}", height);
+
+            let before = harness.contract.ooga_balance_of("alice");
+            assert!(harness.execute(1, vec!["alice".to_string()]).is_ok());
+            assert_eq!(harness.contract.ooga_balance_of("alice") - before, reward, "height {}", height);
+        }
+        assert_eq!(harness.contract.total_ooga(), 8 + 8 + 4 + 4 + 2);
+    }
+
+    #[test]
+    fn test_claim_reward_without_halving() {
+        let harness = reward_harness(0, 3, 0);
+        harness.set_height(1_000_000);
+
+        let response = harness.execute(33, vec![]).unwrap();
+        assert_eq!(extract_u128_at(&response, 0), 3);
+        assert_eq!(extract_u128_at(&response, 1), 0);
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 3);
+    }
+
+    #[test]
+    fn test_claims_fail_once_emission_ends() {
+        let harness = reward_harness(0, 2, 5);
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+        harness.set_height(5);
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 3);
+
+        harness.set_height(10);
+        let response = harness.execute(33, vec![]).unwrap();
+        assert_eq!((extract_u128_at(&response, 0), extract_u128_at(&response, 1)), (0, 0));
+        let (result, diff) = harness.execute_with_diff(1, vec!["alice".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("emission ended"));
+        }
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_claims_before_init_mint_one() {
+        let harness = TestHarness::new();
+        harness.set_height(50);
+        let response = harness.execute(33, vec![]).unwrap();
+        assert_eq!((extract_u128_at(&response, 0), extract_u128_at(&response, 1)), (1, 0));
+        assert!(harness.execute(1, vec!["alice".to_string()]).is_ok());
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 1);
+
+        // An explicit base reward of 0 is recorded, and ends emission from the start
+        let _ = harness.execute(0, vec!["0", "0", "0", "0", "0", "0"].into_iter().map(str::to_string).collect());
+        let result = harness.execute(1, vec!["alice".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("emission ended"));
+        }
+    }
+
+    #[test]
+    fn test_reward_schedule_saturates() {
+        let schedule = RewardSchedule { base_reward: u128::MAX, halving_interval: 1, start: 0 };
+        assert_eq!(schedule.current_claim_reward(127), 1);
+        assert_eq!(schedule.next_halving(127), Some(128));
+        assert_eq!(schedule.current_claim_reward(u64::MAX), 0);
+        assert_eq!(schedule.next_halving(u64::MAX), None);
+
+        // Heights before the schedule starts use the base reward
+        let schedule = RewardSchedule { base_reward: 4, halving_interval: u64::MAX, start: 10 };
+        assert_eq!(schedule.current_claim_reward(0), 4);
+        assert_eq!(schedule.next_halving(0), None);
+    }
+
+    // Initialized harness at height 10 where alice holds 10 OOGA and bob 6 BOOGA
+    fn swap_harness() -> TestHarness {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(19, vec!["0".to_string(), "alice".to_string(), "10".to_string(), "0".to_string()]);
+        let _ = harness.execute(19, vec!["1".to_string(), "bob".to_string(), "6".to_string(), "0".to_string()]);
+        harness.set_height(10);
+        harness
+    }
+
+    // Bob offers 4 BOOGA for 3 OOGA until height 20
+    fn offer_from_bob(harness: &TestHarness) {
+        harness.set_caller("bob");
+        let result = harness.execute(34, vec!["1".to_string(), "4".to_string(), "3".to_string(), "20".to_string()]);
+        assert!(result.is_ok());
+        harness.set_caller("alice");
+    }
+
+    fn take_bob_offer(harness: &TestHarness) -> anyhow::Result<CallResponse> {
+        harness.execute(35, vec!["bob".to_string(), "3".to_string(), "4".to_string()])
+    }
+
+    #[test]
+    fn test_swap_trades_both_legs_and_consumes_offer() {
+        let harness = swap_harness();
+        offer_from_bob(&harness);
+        let offer = harness.execute(36, vec!["bob".to_string()]).unwrap();
+        assert_eq!(
+            SwapOffer::decode(payload(&offer)).unwrap(),
+            SwapOffer { give: Token::Booga, give_amount: 4, want_amount: 3, expiry: 20 }
+        );
+
+        let (result, diff) = harness.execute_with_diff(35, vec!["bob".to_string(), "3".to_string(), "4".to_string()]);
+        assert!(result.is_ok());
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 7);
+        assert_eq!(harness.contract.booga_balance_of("alice"), 4);
+        assert_eq!(harness.contract.ooga_balance_of("bob"), 3);
+        assert_eq!(harness.contract.booga_balance_of("bob"), 2);
+        assert!(diff.removed.contains_key(&harness.contract.swap_offer_key("bob")));
+        assert_eq!(harness.contract.total_ooga(), 10);
+        assert_eq!(harness.contract.total_booga(), 6);
+
+        // The offer can only be taken once
+        assert!(payload(&harness.execute(36, vec!["bob".to_string()]).unwrap()).is_empty());
+        let result = take_bob_offer(&harness);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("no swap offer from bob"));
+        }
+    }
+
+    #[test]
+    fn test_swap_rejects_expired_offer() {
+        let harness = swap_harness();
+        offer_from_bob(&harness);
+
+        // Still valid at the expiry height itself
+        harness.set_height(21);
+        let result = take_bob_offer(&harness);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(error_code(&e), Some(ErrorCode::SwapOfferExpired));
+            assert_eq!(e.to_string(), "swap offer expired at height 20");
+        }
+
+        harness.set_height(20);
+        assert!(take_bob_offer(&harness).is_ok());
+    }
+
+    #[test]
+    fn test_swap_rejects_mismatched_amounts() {
+        let harness = swap_harness();
+        offer_from_bob(&harness);
+
+        for (give, receive) in [("2", "4"), ("3", "5")] {
+            let (result, diff) = harness.execute_with_diff(35, vec!["bob".to_string(), give.to_string(), receive.to_string()]);
+            assert!(result.is_err());
+            if let Err(e) = result {
+                assert_eq!(error_code(&e), Some(ErrorCode::SwapOfferMismatch));
+                assert_eq!(e.to_string(), "swap does not match offer of 4 BOOGA for 3 OOGA");
+            }
+            assert!(diff.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_swap_fails_when_counterparty_balance_drops() {
+        let harness = swap_harness();
+        offer_from_bob(&harness);
+
+        // Bob moves BOOGA away after making the offer
+        harness.set_caller("bob");
+        let _ = harness.execute(14, vec!["carol".to_string(), "3".to_string()]);
+        harness.set_caller("carol");
+        let _ = harness.execute(16, vec!["bob".to_string(), "carol".to_string(), "3".to_string()]);
+        harness.set_caller("alice");
+
+        let (result, diff) = harness.execute_with_diff(35, vec!["bob".to_string(), "3".to_string(), "4".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("counterparty has insufficient BOOGA balance"));
+        }
+        assert!(diff.is_empty());
+        assert!(harness.contract.swap_offer("bob").is_some());
+        assert_eq!(harness.contract.ooga_balance_of("alice"), 10);
+    }
+
+    #[test]
+    fn test_swap_offer_validation() {
+        let harness = swap_harness();
+        harness.set_caller("bob");
+        for (inputs, message) in [
+            (vec!["1", "7", "3", "20"], "insufficient BOOGA balance"),
+            (vec!["1", "4", "0", "20"], "amount must be nonzero"),
+            (vec!["1", "4", "3", "9"], "swap offer expiry 9 is in the past"),
+        ] {
+            let result = harness.execute(34, inputs.into_iter().map(str::to_string).collect());
+            assert!(result.is_err());
+            if let Err(e) = result {
+                assert!(e.to_string().contains(message), "{}", e);
+            }
+        }
+
+        // A zero amount withdraws a standing offer
+        offer_from_bob(&harness);
+        harness.set_caller("bob");
+        let _ = harness.execute(34, vec!["1".to_string(), "0".to_string(), "0".to_string(), "0".to_string()]);
+        assert!(harness.contract.swap_offer("bob").is_none());
+
+        let result = harness.execute(35, vec!["bob".to_string(), "3".to_string(), "4".to_string()]);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("cannot swap with yourself"));
+        }
+    }
+
+    // Read a value of `len` bytes (1, 2, 3, ...) stored under a raw key as a u128
+    fn read_stored_u128(len: usize, strict: bool) -> std::thread::Result<u128> {
+        let harness = TestHarness::new();
+        harness.set_strict_storage(strict);
+        PointerStorage.set_bytes("/raw", (1..=len as u8).collect()).unwrap();
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| harness.contract.read_u128("/raw")))
+    }
+
+    fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+        match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => payload.downcast_ref::<&str>().map(|message| message.to_string()).unwrap_or_default(),
+        }
+    }
+
+    #[test]
+    fn test_lenient_reads_follow_metashrew() {
+        let sixteen = u128::from_le_bytes(core::array::from_fn(|i| i as u8 + 1));
+        assert_eq!(read_stored_u128(0, false).ok(), Some(0));
+        assert_eq!(read_stored_u128(16, false).ok(), Some(sixteen));
+        // Metashrew converts the whole value, so shorter and longer ones both trap on chain
+        assert!(read_stored_u128(8, false).is_err());
+        let message = panic_message(read_stored_u128(24, false).unwrap_err());
+        assert!(!message.contains("strict storage"), "{}", message);
+    }
+
+    #[test]
+    fn test_strict_reads_reject_mismatched_lengths() {
+        let sixteen = u128::from_le_bytes(core::array::from_fn(|i| i as u8 + 1));
+        assert_eq!(read_stored_u128(0, true).ok(), Some(0));
+        assert!(read_stored_u128(8, true).is_err());
+        assert_eq!(read_stored_u128(16, true).ok(), Some(sixteen));
+        let message = panic_message(read_stored_u128(24, true).unwrap_err());
+        assert!(message.contains("strict storage: /raw holds 24 bytes"), "{}", message);
+    }
+
+    #[test]
+    fn test_harness_enables_strict_storage() {
+        let harness = TestHarness::new();
+        assert!(harness.strict_storage());
+
+        // Strict mode belongs to the harness's storage, not to the thread
+        harness.set_strict_storage(false);
+        assert!(TestHarness::new().strict_storage());
+        assert!(!harness.strict_storage());
+    }
+
+    #[test]
+    fn test_strict_storage_covers_every_read_path() {
+        let harness = TestHarness::new();
+        let key = harness.contract.ooga_balance_key("alice");
+        PointerStorage.set_bytes(&key, vec![1; 24]).unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| harness.contract.ooga_balance_of("alice")));
+        assert!(panic_message(result.unwrap_err()).contains("strict storage"));
+
+        let key = harness.contract.last_active_key("alice");
+        PointerStorage.set_bytes(&key, vec![1; 24]).unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| harness.contract.activity("alice", 0)));
+        assert!(panic_message(result.unwrap_err()).contains("strict storage"));
+
+        // Values staged by the operation in progress are checked before they are committed
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            harness.contract.atomically(|| {
+                harness.contract.write_bytes("/staged", vec![1; 8]);
+                Ok(harness.contract.read_u128("/staged"))
+            })
+        }));
+        assert!(panic_message(result.unwrap_err()).contains("strict storage: /staged"));
+    }
+
+    // The sequence of tests/test_contract.rs::test_multi_user_interaction, traced
+    #[cfg(feature = "trace")]
+    #[test]
+    fn test_multi_user_interaction_trace() {
+        use crate::test_utils::trace::pretty_trace;
+
+        let harness = TestHarness::new();
+        // Init writes its parameters, supply totals, schema version and reward schedule
+        let mut expected_writes = Vec::new();
+        let _ = harness.execute(0, vec![]);
+        expected_writes.push(12);
+        for (address, claims) in [("alice", 3), ("bob", 2), ("charlie", 1)] {
+            for claim in 0..claims {
+                let _ = harness.execute(1, vec![address.to_string()]);
+                // A first claim also registers the holder
+                expected_writes.push(if claim == 0 { 7 } else { 4 });
+            }
+        }
+        let _ = harness.execute(5, vec![]);
+        expected_writes.push(0);
+        for (address, exchanges) in [("alice", 2), ("bob", 1)] {
+            for _ in 0..exchanges {
+                let _ = harness.execute(2, vec![address.to_string()]);
+            }
+            // Both balances, both totals and the activity height
+            expected_writes.extend(vec![5; exchanges]);
+        }
+        for address in ["alice", "bob"] {
+            let _ = harness.execute(3, vec![address.to_string()]);
+            let _ = harness.execute(4, vec![address.to_string()]);
+            expected_writes.extend([0, 0]);
+        }
+        let _ = harness.execute(5, vec![]);
+        let _ = harness.execute(6, vec![]);
+        expected_writes.extend([0, 0]);
+
+        let trace = harness.take_trace();
+        let writes: Vec<usize> = trace.iter().map(|entry| entry.writes.len()).collect();
+        assert_eq!(writes, expected_writes, "{}", pretty_trace(&trace));
+        assert!(harness.take_trace().is_empty());
+
+        // Queries only read, and report what they returned
+        let claim = &trace[1];
+        assert_eq!(claim.params, vec![("address".to_string(), "\"alice\"".to_string()), ("referrer".to_string(), "None".to_string())]);
+        assert!(claim.writes.iter().any(|write| write.key == "/ooga-balance/alice" && write.old.is_none()));
+        let total = &trace[trace.len() - 2];
+        assert!(total.reads.iter().any(|read| read.key == "/total-ooga"));
+        assert_eq!(total.result, Ok(3u128.to_le_bytes().to_vec()));
+
+        let printed = pretty_trace(&trace);
+        assert!(printed.starts_with("#0 opcode 0 (Initialize) by deployer at height 0\n"));
+        assert!(printed.contains("  write /total-ooga: 0x01000000000000000000000000000000 (1) -> 0x02000000000000000000000000000000 (2)\n"));
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn test_trace_records_staged_reads() {
+        use crate::test_utils::trace::pretty_trace;
+
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec!["0".to_string(), "5".to_string()]);
+        let _ = harness.execute(1, vec!["alice".to_string()]);
+        let _ = harness.take_trace();
+        let _ = harness.execute(1, vec!["alice".to_string(), "bob".to_string()]);
+        let trace = harness.take_trace();
+        let claim = &trace[0];
+
+        // Minting the referral bonus reads the total the claim itself just staged
+        let staged: Vec<_> = claim.reads.iter().filter(|read| read.staged).collect();
+        assert_eq!(staged.len(), 1, "{}", pretty_trace(&trace));
+        assert_eq!(staged[0].key, "/total-ooga");
+        assert_eq!(staged[0].value, 2u128.to_le_bytes().to_vec());
+        assert!(pretty_trace(&trace).contains("  read  /total-ooga = 0x02000000000000000000000000000000 (2) (staged)\n"));
+
+        // Every other read reached storage, as the metered backend counted
+        assert_eq!(claim.reads.len() - staged.len(), harness.last_op_metrics().reads);
+    }
+
+    // Boundary values for the input decoders, followed by a deterministic
+    // spread of values of every magnitude
+    fn decoder_inputs() -> Vec<u128> {
+        let mut values = vec![
+            0, 1, 2,
+            MAX_PAGE_SIZE - 1, MAX_PAGE_SIZE, MAX_PAGE_SIZE + 1,
+            u64::MAX as u128 - 1, u64::MAX as u128, u64::MAX as u128 + 1,
+            u128::MAX - 1, u128::MAX,
+        ];
+        let mut state: u128 = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..1000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            values.push(state >> (state % 128));
+        }
+        values
+    }
+
+    fn code_of<T>(result: &anyhow::Result<T>) -> Option<ErrorCode> {
+        result.as_ref().err().map(ErrorCode::of)
+    }
+
+    #[test]
+    fn test_input_decoders_classify_boundaries() {
+        for value in decoder_inputs() {
+            let amount = decode_amount_nonzero(value);
+            match value {
+                0 => assert_eq!(code_of(&amount), Some(ErrorCode::ZeroAmount)),
+                _ => assert_eq!(amount.ok(), Some(value)),
+            }
+
+            let index = decode_index(value);
+            if value <= u64::MAX as u128 {
+                assert_eq!(index.ok(), Some(value as u64));
+            } else {
+                assert_eq!(code_of(&index), Some(ErrorCode::IndexOutOfRange), "index {}", value);
+            }
+
+            let limit = decode_limit(value, MAX_PAGE_SIZE);
+            if (1..=MAX_PAGE_SIZE).contains(&value) {
+                assert_eq!(limit.ok(), Some(value));
+            } else {
+                assert_eq!(code_of(&limit), Some(ErrorCode::LimitOutOfRange), "limit {}", value);
+            }
+            assert_eq!(decode_limit(value, u128::MAX).is_ok(), value != 0);
+
+            let opcode = decode_opcode(value);
+            match Opcode::ALL.iter().find(|opcode| opcode.code() == value) {
+                Some(expected) => assert_eq!(opcode.ok(), Some(*expected)),
+                None => assert_eq!(code_of(&opcode), Some(ErrorCode::UnrecognizedOpcode), "opcode {}", value),
+            }
+        }
+
+        let error = decode_limit(u128::MAX, MAX_PAGE_SIZE).unwrap_err();
+        assert_eq!(error.to_string(), format!("limit {} out of range 1..={}", u128::MAX, MAX_PAGE_SIZE));
+        let error = decode_index(u128::MAX).unwrap_err();
+        assert_eq!(error.to_string(), format!("index {} out of range", u128::MAX));
+    }
+
+    // Dispatch a decoded opcode with the given caller, as either runtime does
+    fn dispatch_as<I: OpcodeInputs>(harness: &TestHarness, caller: &str, opcode: Opcode, inputs: I) -> anyhow::Result<Vec<u8>> {
+        let mut call = Call { inputs, caller: caller.to_string(), height: 0, incoming_alkanes: Vec::new() };
+        harness.contract.atomically(|| dispatch(&harness.contract, opcode, &mut call)).map(|output| output.data)
+    }
+
+    #[test]
+    fn test_input_flavors_classify_identically() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        // u128 inputs name addresses by their (block, tx) pair
+        for _ in 0..3 {
+            let _ = harness.execute(1, vec!["7:0".to_string()]);
+        }
+
+        // Each case varies the input marked 'v' over the decoder inputs
+        let cases: Vec<(Opcode, Vec<&str>)> = vec![
+            (Opcode::Exchange, vec!["7:0", "v"]),
+            (Opcode::CreateVestingGrant, vec!["8:0", "v", "0", "10"]),
+            (Opcode::EventsSince, vec!["v", "10"]),
+            (Opcode::EventsSince, vec!["0", "v"]),
+            (Opcode::AddressEvents, vec!["7:0", "v", "10"]),
+            (Opcode::AddressEvents, vec!["7:0", "0", "v"]),
+            (Opcode::CheckInvariants, vec!["v"]),
+            (Opcode::CheckInvariants, vec!["0", "v"]),
+            (Opcode::QuoteExchange, vec!["7:0", "v"]),
+        ];
+        for (opcode, template) in cases {
+            for value in decoder_inputs().into_iter().take(40) {
+                let numbers: Vec<u128> = template.iter()
+                    .flat_map(|input| input.split(':'))
+                    .map(|input| if input == "v" { value } else { input.parse().unwrap() })
+                    .collect();
+                let strings: Vec<String> = template.iter()
+                    .map(|input| if *input == "v" { value.to_string() } else { input.to_string() })
+                    .collect();
+
+                let before = snapshot_storage();
+                let from_numbers = dispatch_as(&harness, DEFAULT_CALLER, opcode, numbers);
+                restore_storage(before.clone());
+                let from_strings = dispatch_as(&harness, DEFAULT_CALLER, opcode, strings);
+                restore_storage(before);
+
+                assert_eq!(code_of(&from_numbers), code_of(&from_strings), "{:?} with {}", opcode, value);
+                assert_eq!(from_numbers.ok(), from_strings.ok(), "{:?} with {}", opcode, value);
+            }
+        }
+
+        // TransferMany takes at most a page of recipient pairs in either flavor
+        let _ = harness.execute(19, vec!["0".to_string(), "7:0".to_string(), MAX_PAGE_SIZE.to_string(), "0".to_string()]);
+        for count in [MAX_PAGE_SIZE, MAX_PAGE_SIZE + 1] {
+            let numbers: Vec<u128> = (0..count).flat_map(|index| [9, index, 1]).collect();
+            let strings: Vec<String> = (0..count).flat_map(|index| [format!("9:{}", index), "1".to_string()]).collect();
+
+            let before = snapshot_storage();
+            let from_numbers = dispatch_as(&harness, "7:0", Opcode::TransferMany, numbers);
+            restore_storage(before.clone());
+            let from_strings = dispatch_as(&harness, "7:0", Opcode::TransferMany, strings);
+            restore_storage(before);
+
+            let expected = (count > MAX_PAGE_SIZE).then_some(ErrorCode::LimitOutOfRange);
+            assert_eq!(code_of(&from_numbers), expected, "{} pairs", count);
+            assert_eq!(code_of(&from_strings), expected, "{} pairs", count);
+        }
+
+        // Unknown opcode numbers are rejected the same way however large they are
+        for code in [37, 255, 256, 300, u64::MAX as u128, u128::MAX] {
+            let error = harness.execute(code, vec![]).unwrap_err();
+            assert_eq!(error_code(&error), code_of(&decode_opcode(code)));
+            assert_eq!(error_code(&error), Some(ErrorCode::UnrecognizedOpcode));
+        }
+
+        // Oversized pages are refused rather than truncated
+        let error = harness.execute(20, vec!["0".to_string(), u128::MAX.to_string()]).unwrap_err();
+        assert_eq!(error_code(&error), Some(ErrorCode::LimitOutOfRange));
+        let error = harness.execute(22, vec!["7:0".to_string(), u128::MAX.to_string(), "1".to_string()]).unwrap_err();
+        assert_eq!(error_code(&error), Some(ErrorCode::IndexOutOfRange));
+        let error = harness.execute(8, vec!["8:0".to_string(), "0".to_string(), "0".to_string(), "10".to_string()]).unwrap_err();
+        assert_eq!(error_code(&error), Some(ErrorCode::ZeroAmount));
+    }
+
+    #[test]
+    fn test_allowances_match_alkanes_callers() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        let owner = AlkaneId { block: 2, tx: 1 };
+        let spender = AlkaneId { block: 2, tx: 5 };
+        let recipient = AlkaneId { block: 2, tx: 9 };
+
+        // u128 address inputs name the same keys the runtime's caller does
+        assert_eq!(alkane_address(&owner), "2:1");
+        for _ in 0..3 {
+            assert!(dispatch_as(&harness, "2:1", Opcode::Claim, vec![2u128, 1]).is_ok());
+        }
+        assert!(dispatch_as(&harness, "2:1", Opcode::Exchange, vec![2u128, 1, 1]).is_ok());
+        assert_eq!(harness.contract.ooga_balance_of("2:1"), 2);
+
+        // The owner approves as a caller and the spender is named by its inputs
+        let caller = alkane_address(&owner);
+        assert!(dispatch_as(&harness, &caller, Opcode::ApproveOoga, vec![2u128, 5, 2]).is_ok());
+        assert!(dispatch_as(&harness, &caller, Opcode::ApproveBooga, vec![2u128, 5, 1]).is_ok());
+        assert_eq!(harness.contract.allowance(Token::Ooga, "2:1", "2:5"), 2);
+
+        let caller = alkane_address(&spender);
+        let result = dispatch_as(&harness, &caller, Opcode::TransferOogaFrom, vec![2u128, 1, 2, 9, 2]);
+        assert!(result.is_ok(), "{:?}", result.err());
+        let result = dispatch_as(&harness, &caller, Opcode::TransferBoogaFrom, vec![2u128, 1, 2, 9, 1]);
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        let recipient = alkane_address(&recipient);
+        assert_eq!(harness.contract.ooga_balance_of(&recipient), 2);
+        assert_eq!(harness.contract.booga_balance_of(&recipient), 1);
+        assert_eq!(harness.contract.ooga_balance_of("2:1"), 0);
+        assert_eq!(harness.contract.allowance(Token::Ooga, "2:1", "2:5"), 0);
+        assert_eq!(harness.contract.allowance(Token::Booga, "2:1", "2:5"), 0);
+    }
+
+    #[test]
+    fn test_transfer_many_pays_from_alkanes_caller() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        for _ in 0..3 {
+            let _ = dispatch_as(&harness, "2:1", Opcode::Claim, vec![2u128, 1]);
+        }
+
+        // The sender is the caller's block:tx, the same key its claims credited
+        let sender = alkane_address(&AlkaneId { block: 2, tx: 1 });
+        let result = dispatch_as(&harness, &sender, Opcode::TransferMany, vec![2u128, 5, 1, 2, 9, 2]);
+        assert!(result.is_ok(), "{:?}", result.err());
+        assert_eq!(harness.contract.ooga_balance_of("2:1"), 0);
+        assert_eq!(harness.contract.ooga_balance_of("2:5"), 1);
+        assert_eq!(harness.contract.ooga_balance_of("2:9"), 2);
+    }
+
+    #[test]
+    fn test_transfer_many_caps_recipients() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        let _ = harness.execute(19, vec!["0".to_string(), DEFAULT_CALLER.to_string(), MAX_PAGE_SIZE.to_string(), "0".to_string()]);
+        let pairs = |count: u128| (0..count).flat_map(|index| [format!("r{}", index), "1".to_string()]).collect::<Vec<_>>();
+
+        assert!(harness.execute(26, pairs(MAX_PAGE_SIZE)).is_ok());
+        let error = harness.execute(26, pairs(MAX_PAGE_SIZE + 1)).unwrap_err();
+        assert_eq!(error_code(&error), Some(ErrorCode::LimitOutOfRange));
+        assert_eq!(error.to_string(), format!("limit {} out of range 1..={}", MAX_PAGE_SIZE + 1, MAX_PAGE_SIZE));
+    }
+
+    // Zero amounts are rejected while decoding, before any state is touched
+    fn assert_zero_rejected(harness: &TestHarness, opcode: u128, inputs: &[&str]) {
+        let (result, diff) = harness.execute_with_diff(opcode, inputs.iter().map(|input| input.to_string()).collect());
+        assert_eq!(result.as_ref().err().and_then(error_code), Some(ErrorCode::ZeroAmount), "opcode {}", opcode);
+        assert!(diff.is_empty(), "opcode {}: {:?}", opcode, diff);
+    }
+
+    #[test]
+    fn test_amount_inputs_classify_boundaries() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        for address in ["alice", "alice", "alice", "bob", "bob"] {
+            let _ = harness.execute(1, vec![address.to_string()]);
+        }
+        let _ = harness.execute(2, vec!["alice".to_string()]);
+        let _ = harness.execute(2, vec!["bob".to_string()]);
+        harness.set_caller("alice");
+        let _ = harness.execute(11, vec!["bob".to_string(), "5".to_string()]);
+        let _ = harness.execute(14, vec!["bob".to_string(), "5".to_string()]);
+        let max = u128::MAX.to_string();
+        let failed = |opcode: u128, inputs: &[&str]| {
+            let error = harness.execute(opcode, inputs.iter().map(|input| input.to_string()).collect()).unwrap_err();
+            error_code(&error)
+        };
+
+        // Spending an allowance: MAX decodes and then exceeds it
+        harness.set_caller("bob");
+        for opcode in [13, 16] {
+            assert_zero_rejected(&harness, opcode, &["alice", "carol", "0"]);
+            assert_eq!(failed(opcode, &["alice", "carol", &max]), Some(ErrorCode::Failed));
+            assert!(harness.execute(opcode, vec!["alice".to_string(), "carol".to_string(), "1".to_string()]).is_ok());
+        }
+
+        // A zero pair anywhere in the batch rejects the whole payment
+        assert_zero_rejected(&harness, 26, &["carol", "1", "dave", "0"]);
+        assert_eq!(failed(26, &["carol", &max]), Some(ErrorCode::Failed));
+        assert!(harness.execute(26, vec!["carol".to_string(), "1".to_string()]).is_ok());
+
+        // An offer that gives something must want a nonzero amount
+        harness.set_caller("alice");
+        assert_zero_rejected(&harness, 34, &["0", "1", "0", "10"]);
+        assert!(harness.execute(34, vec!["0".to_string(), "1".to_string(), "1".to_string(), "10".to_string()]).is_ok());
+
+        // Both legs of a swap are amounts
+        harness.set_caller("bob");
+        assert_zero_rejected(&harness, 35, &["alice", "0", "1"]);
+        assert_zero_rejected(&harness, 35, &["alice", "1", "0"]);
+        assert_eq!(failed(35, &["alice", &max, "1"]), Some(ErrorCode::SwapOfferMismatch));
+        assert!(harness.execute(35, vec!["alice".to_string(), "1".to_string(), "1".to_string()]).is_ok());
+    }
+
+    #[test]
+    fn test_swap_between_alkanes_callers() {
+        let harness = TestHarness::new();
+        let _ = harness.execute(0, vec![]);
+        let (offerer, taker) = (AlkaneId { block: 2, tx: 1 }, AlkaneId { block: 2, tx: 5 });
+        let (offerer, taker) = (alkane_address(&offerer), alkane_address(&taker));
+        for _ in 0..2 {
+            let _ = dispatch_as(&harness, &offerer, Opcode::Claim, vec![2u128, 1]);
+            let _ = dispatch_as(&harness, &taker, Opcode::Claim, vec![2u128, 5]);
+        }
+        let _ = dispatch_as(&harness, &taker, Opcode::Exchange, vec![2u128, 5, 1]);
+
+        // The offer is stored under the offerer's caller address and found by the pair naming it
+        let offer = vec![Token::Ooga.code() as u128, 2, 1, 100];
+        assert!(dispatch_as(&harness, &offerer, Opcode::OfferSwap, offer).is_ok());
+        let stored = dispatch_as(&harness, &taker, Opcode::SwapOffer, vec![2u128, 1]).unwrap();
+        assert_eq!(SwapOffer::decode(&stored).unwrap().give_amount, 2);
+
+        let result = dispatch_as(&harness, &taker, Opcode::Swap, vec![2u128, 1, 1, 2]);
+        assert!(result.is_ok(), "{:?}", result.err());
+        assert_eq!(harness.contract.ooga_balance_of(&offerer), 0);
+        assert_eq!(harness.contract.booga_balance_of(&offerer), 1);
+        assert_eq!(harness.contract.ooga_balance_of(&taker), 3);
+        assert_eq!(harness.contract.booga_balance_of(&taker), 0);
+        assert!(harness.contract.swap_offer(&offerer).is_none());
+    }
 }
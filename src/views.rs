@@ -0,0 +1,49 @@
+#[cfg(feature = "views")]
+use serde::Serialize;
+
+use crate::OogaBoogaContract;
+
+/// Balances and activity of a single address, for native embedders
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "views", derive(Serialize))]
+pub struct AccountView {
+    pub address: String,
+    pub ooga: u128,
+    pub booga: u128,
+    /// Height of the address's last mutating operation, if it has had one
+    pub last_active: Option<u64>,
+}
+
+/// Token supply and holder totals, for native embedders
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "views", derive(Serialize))]
+pub struct SupplyView {
+    pub total_ooga: u128,
+    pub total_booga: u128,
+    /// OOGA supply cap (0 = uncapped)
+    pub max_supply: u128,
+    /// Addresses currently holding OOGA or BOOGA, as counted by the holder index
+    pub holders: u128,
+}
+
+// Typed read-only views over the same storage the query opcodes read
+impl OogaBoogaContract {
+    pub fn account_view(&self, address: &str) -> AccountView {
+        let activity = self.activity(address, 0);
+        AccountView {
+            address: address.to_string(),
+            ooga: self.ooga_balance_of(address),
+            booga: self.booga_balance_of(address),
+            last_active: activity.seen.then_some(activity.last_active),
+        }
+    }
+
+    pub fn supply_view(&self) -> SupplyView {
+        SupplyView {
+            total_ooga: self.total_ooga(),
+            total_booga: self.total_booga(),
+            max_supply: self.max_supply(),
+            holders: self.holder_count(),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+#![cfg(all(feature = "cli", not(feature = "alkanes")))]
+
+use assert_cmd::Command;
+use std::path::PathBuf;
+
+// Unique state file per test so parallel runs do not share storage
+fn state_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ooga-cli-{}-{}.json", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn cli(state: &PathBuf) -> Command {
+    let mut command = Command::cargo_bin("ooga-cli").unwrap();
+    command.arg("--state").arg(state);
+    command
+}
+
+fn stdout_of(command: &mut Command) -> String {
+    let output = command.assert().success().get_output().stdout.clone();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn test_cli_claim_exchange_flow() {
+    let state = state_path("flow");
+
+    let output = stdout_of(cli(&state).args(["--caller", "deployer", "init"]));
+    assert!(output.contains("schema version 1"));
+    assert!(output.contains("owner deployer"));
+
+    // State persists across invocations through the file
+    for _ in 0..3 {
+        cli(&state).args(["claim", "alice"]).assert().success();
+    }
+    let output = stdout_of(cli(&state).args(["exchange", "alice"]));
+    assert!(output.contains("OOGA 2, BOOGA 1"));
+
+    let output = stdout_of(cli(&state).args(["balance", "alice"]));
+    assert_eq!(output.trim(), "alice: OOGA 2, BOOGA 1");
+
+    let output = stdout_of(cli(&state).arg("totals"));
+    assert!(output.contains("total OOGA: 2"));
+    assert!(output.contains("total BOOGA: 1"));
+
+    let _ = std::fs::remove_file(&state);
+}
+
+#[test]
+fn test_cli_init_echoes_every_parameter() {
+    let state = state_path("init");
+    let output = stdout_of(cli(&state).args(["init", "1", "2", "3", "4", "5", "6", "7"]));
+    assert!(output.contains("claim cap 1"));
+    assert!(output.contains("referral bonus 2, max supply 3, claim window 4..5, base reward 6, halving interval 7"));
+
+    let _ = std::fs::remove_file(&state);
+}
+
+#[test]
+fn test_cli_reports_errors_without_saving() {
+    let state = state_path("errors");
+    cli(&state).arg("init").assert().success();
+
+    // Exchanging without OOGA fails with the contract error and its code
+    let output = cli(&state).args(["exchange", "bob"]).assert().failure().code(1).get_output().stderr.clone();
+    assert!(String::from_utf8(output).unwrap().contains("error: Failed: insufficient OOGA balance"));
+
+    // Re-initializing is rejected and leaves the state untouched
+    let output = cli(&state).args(["init", "5"]).assert().failure().code(1).get_output().stderr.clone();
+    assert!(String::from_utf8(output).unwrap().contains("error: AlreadyInitialized: contract already initialized"));
+    let output = stdout_of(cli(&state).args(["call", "7"]));
+    assert_eq!(&output.trim()[..32], "00000000000000000000000000000000");
+
+    // Unknown and missing commands are usage errors
+    let output = cli(&state).arg("bogus").assert().failure().code(2).get_output().stderr.clone();
+    assert!(String::from_utf8(output).unwrap().contains("usage: ooga-cli"));
+    cli(&state).assert().failure().code(2);
+
+    let _ = std::fs::remove_file(&state);
+}
+
+#[test]
+fn test_cli_state_flag_selects_file() {
+    let first = state_path("first");
+    let second = state_path("second");
+    cli(&first).arg("init").assert().success();
+    cli(&first).args(["claim", "alice"]).assert().success();
+    cli(&second).arg("init").assert().success();
+
+    let output = stdout_of(cli(&first).args(["balance", "alice"]));
+    assert_eq!(output.trim(), "alice: OOGA 1, BOOGA 0");
+    let output = stdout_of(cli(&second).args(["balance", "alice"]));
+    assert_eq!(output.trim(), "alice: OOGA 0, BOOGA 0");
+
+    let _ = std::fs::remove_file(&first);
+    let _ = std::fs::remove_file(&second);
+}
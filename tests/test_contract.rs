@@ -5,7 +5,7 @@ use std::cell::RefCell;
 // Thread-local storage for testing to avoid deadlocks
 thread_local! {
     static MOCK_STORAGE: RefCell<HashMap<String, Vec<u8>>> = RefCell::new(HashMap::new());
-    static CONTEXT: RefCell<Option<Context>> = RefCell::new(None);
+    static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
 }
 
 // Mock implementation of Context for testing
@@ -19,6 +19,8 @@ struct Context {
 #[derive(Debug)]
 struct CallResponse {
     pub data: Vec<u8>,
+    // Mirrors the runtime's outgoing transfers; these tests only check `data`
+    #[allow(dead_code)]
     pub alkanes: (Vec<u8>, Vec<u8>),
 }
 